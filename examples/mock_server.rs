@@ -0,0 +1,68 @@
+use planetside2_event_client::{
+    data::{Character, Environment, World},
+    event::{
+        message::EventRequest, CallbackHolder, EventPayload, EventStreamingClient, EventType,
+        ReconnectPolicy,
+    },
+    testing::MockPushServer,
+};
+
+use futures_util::StreamExt;
+use serde_json::json;
+
+const SERVICE_ID: &str = "example";
+
+fn login(character: u64) -> serde_json::Value {
+    json!({
+        "event_name": "PlayerLogin",
+        "character_id": character.to_string(),
+        "timestamp": "1700000000",
+        "world_id": "17",
+    })
+}
+
+#[tokio::main]
+async fn main() {
+    // start a stand-in for the push server, no service id or internet needed
+    let server = MockPushServer::start().await.unwrap();
+    server.set_recent_characters(vec![Character(1), Character(2)]);
+
+    let mut event_client = EventStreamingClient::new(
+        Environment::PC,
+        SERVICE_ID.to_owned(),
+        CallbackHolder::new(),
+    )
+    .with_url(server.url())
+    .with_reconnect_policy(ReconnectPolicy::default());
+    event_client.connect().await.unwrap();
+    event_client
+        .send_request(
+            EventRequest::subscribe()
+                .world(World::Emerald)
+                .event(EventType::PlayerLogin)
+                .build(),
+        )
+        .await
+        .unwrap();
+
+    // a single event, two events sent together in one websocket message, then a dropped connection the client recovers from
+    server.send_event(login(1));
+    server.send_events_concatenated([login(2), login(3)]);
+    server.disconnect();
+    server.send_event(login(4));
+
+    let mut events = event_client.event_stream();
+    let mut logins = vec![];
+    while logins.len() < 4 {
+        if let Some(EventPayload::PlayerLogin(e)) = events.next().await {
+            logins.push(e.character_id);
+        }
+    }
+    assert_eq!(logins, [1, 2, 3, 4].map(Character));
+    println!("got logins for {:?}", logins);
+
+    // the stream only borrowed the client, so it can still ask the server things
+    drop(events);
+    let recent = event_client.recent_character_ids().await.unwrap();
+    println!("recently online: {:?}", recent);
+}
@@ -1,10 +1,7 @@
 use planetside2_event_client::{
     census::CensusClient,
     data::{Character, Environment, World},
-    event::{
-        message::{EventRequest, EventRequestAction, Service},
-        CallbackHolder, EventPayload, EventStreamingClient, EventType,
-    },
+    event::{message::EventRequest, CallbackHolder, EventPayload, EventStreamingClient, EventType},
 };
 
 use tokio::{self, sync::mpsc};
@@ -82,27 +79,17 @@ async fn listen_to_events(
 #[tokio::main]
 async fn main() {
     // get the id of a character from their name
-    let mut census_client = CensusClient::new(SERVICE_ID.to_owned());
+    let mut census_client = CensusClient::new(Environment::PC, SERVICE_ID.to_owned());
     let character = Character::from_name(USERNAME.to_owned(), &mut census_client)
         .await
         .unwrap();
 
-    let events = vec![EventType::GainExperience, EventType::Death];
-    let worlds = vec![World::Miller];
-    let characters = vec![character];
-    let character_ids: Vec<String> = characters.iter().map(|c| c.to_string()).collect();
-
     // construct a request to send to the server
-    let sub_request = EventRequest {
-        action: EventRequestAction::Subscribe,
-        service: Service::Event,
-        characters: Some(character_ids),
-        worlds: Some(worlds),
-        event_names: Some(events),
-        all: None,
-        list_characters: None,
-        payload: None,
-    };
+    let sub_request = EventRequest::subscribe()
+        .character(character)
+        .world(World::Miller)
+        .events([EventType::GainExperience, EventType::Death])
+        .build();
 
     // create a channel to pass received events through
     let (chan_send, chan_receive) = tokio::sync::mpsc::unbounded_channel::<EventPayload>();
@@ -119,8 +106,11 @@ async fn main() {
     event_client.send_request(sub_request).await.unwrap();
 
     // run the client and the channel listener
-    tokio::join!(
+    let (result, _) = tokio::join!(
         event_client.run(),
         listen_to_events(chan_receive, census_client, character)
     );
+    if let Err(e) = result {
+        println!("Stopped: {}", e);
+    }
 }
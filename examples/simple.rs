@@ -2,75 +2,62 @@ use planetside2_event_client::{
     census::CensusClient,
     data::{Character, Environment, World},
     event::{
-        message::{EventRequest, EventRequestAction, Service},
-        CallbackHolder, EventPayload, EventStreamingClient, EventType,
+        message::{
+            events::{DeathEvent, ExperienceEvent},
+            EventRequest,
+        },
+        CallbackHolder, EventStreamingClient, EventType,
     },
 };
 
-use tokio;
-
 const SERVICE_ID: &str = "example";
 
 // fill in a username here
 const USERNAME: &str = "something";
 const ENVIRONMENT: Environment = Environment::PC;
 
-fn handle_event_message(event: &EventPayload, character: Character) {
-    println!("Received event message: {:?}", event);
-    match event {
-        EventPayload::Death(d) => {
-            if d.character_id == character {
-                println!("You just got killed");
-            } else if d.attacker_character_id == character {
-                println!("You just killed someone");
-            }
-            if d.is_headshot {
-                println!("Kill was a headshot");
-            }
-        }
-        EventPayload::GainExperience(e) => {
-            if e.character_id == character {
-                // you earned the experience
-                println!("You just earned experience");
+fn handle_death(d: &DeathEvent, character: Character) {
+    if d.character_id == character {
+        println!("You just got killed");
+    } else if d.attacker_character_id == character {
+        println!("You just killed someone");
+    }
+    if d.is_headshot {
+        println!("Kill was a headshot");
+    }
+}
+
+fn handle_experience(e: &ExperienceEvent, character: Character) {
+    if e.character_id == character {
+        // you earned the experience
+        println!("You just earned experience");
 
-                if e.experience_id.0 == 1 {
-                    println!("You just earned kill experience");
-                }
-            }
+        if e.experience_id.0 == 1 {
+            println!("You just earned kill experience");
         }
-        _ => (), //ignore all other events
     }
 }
 
 #[tokio::main]
 async fn main() {
     // get the id of a character from their name
-    let mut census_client = CensusClient::new(SERVICE_ID.to_owned());
+    let mut census_client = CensusClient::new(Environment::PC, SERVICE_ID.to_owned());
     let character = Character::from_name(USERNAME.to_owned(), &mut census_client)
         .await
         .unwrap();
 
-    let events = vec![EventType::GainExperience, EventType::Death];
-    let worlds = vec![World::Miller];
-    let characters = vec![character];
-    let character_ids: Vec<String> = characters.iter().map(|c| c.to_string()).collect();
-
     // construct a request to send to the server
-    let sub_request = EventRequest {
-        action: EventRequestAction::Subscribe,
-        service: Service::Event,
-        characters: Some(character_ids),
-        worlds: Some(worlds),
-        event_names: Some(events),
-        all: None,
-        list_characters: None,
-        payload: None,
-    };
+    let sub_request = EventRequest::subscribe()
+        .character(character)
+        .world(World::Miller)
+        .events([EventType::GainExperience, EventType::Death])
+        .build();
 
     // add our callback to an object to call them
     let mut callbacks = CallbackHolder::new();
-    callbacks.register_event_listener(move |e| handle_event_message(e, character));
-    callbacks.register_event_listener(move |e| println!("{:?}", e));
+    callbacks.register_typed_event_listener(move |d| handle_death(d, character));
+    callbacks.register_typed_event_listener(move |e| handle_experience(e, character));
+    callbacks.register_event_listener(move |e| println!("Received event message: {:?}", e));
 
     // create a client and connect it to the server
     let mut event_client = EventStreamingClient::new(ENVIRONMENT, SERVICE_ID.to_owned(), callbacks);
@@ -79,6 +66,8 @@ async fn main() {
     // send thee request constructed earlier
     event_client.send_request(sub_request).await.unwrap();
 
-    // run the client
-    event_client.run().await;
+    // run the client, it only stops with an error if reconnecting fails
+    if let Err(e) = event_client.run().await {
+        println!("Stopped: {}", e);
+    }
 }
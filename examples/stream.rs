@@ -0,0 +1,61 @@
+use planetside2_event_client::{
+    census::CensusClient,
+    data::{Character, Environment, World},
+    event::{message::EventRequest, CallbackHolder, EventPayload, EventStreamingClient, EventType},
+};
+
+use futures_util::{future, StreamExt};
+
+const SERVICE_ID: &str = "example";
+
+// fill in a username here
+const USERNAME: &str = "something";
+const ENVIRONMENT: Environment = Environment::PC;
+
+#[tokio::main]
+async fn main() {
+    // get the id of a character from their name
+    let mut census_client = CensusClient::new(Environment::PC, SERVICE_ID.to_owned());
+    let character = Character::from_name(USERNAME.to_owned(), &mut census_client)
+        .await
+        .unwrap();
+
+    // construct a request to send to the server
+    let sub_request = EventRequest::subscribe()
+        .character(character)
+        .world(World::Miller)
+        .event(EventType::Death)
+        .build();
+
+    // create a client without any callbacks and connect it to the server
+    let mut event_client =
+        EventStreamingClient::new(ENVIRONMENT, SERVICE_ID.to_owned(), CallbackHolder::new());
+    event_client.connect().await.unwrap();
+    event_client.send_request(sub_request).await.unwrap();
+
+    // the stream can be moved to another task and used with all the usual stream combinators
+    let deaths = event_client
+        .into_event_stream()
+        .filter_map(|e| {
+            future::ready(match e {
+                EventPayload::Death(d) => Some(d),
+                _ => None,
+            })
+        })
+        .take(10);
+
+    let handle = tokio::spawn(async move {
+        let mut deaths = deaths;
+        while let Some(d) = deaths.next().await {
+            if d.character_id == character {
+                // the census client can be used directly, no channel needed
+                if let Some(other) = d.attacker_character_id.info(&mut census_client).await {
+                    println!("You just got killed by {}", other.name.first);
+                }
+            } else {
+                println!("You just killed someone");
+            }
+        }
+    });
+    handle.await.unwrap();
+}
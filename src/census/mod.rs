@@ -1,18 +1,19 @@
-const CENSUS_BASE_URL: &str = "http://census.daybreakgames.com/s:{service_id}/json/get/ps2:v2/";
+const CENSUS_BASE_URL: &str =
+    "http://census.daybreakgames.com/s:{service_id}/json/get/{namespace}:v2/";
 
 use std::collections::HashMap;
-use std::error::Error;
 use std::str::FromStr;
 
 use reqwest::Client;
+use serde::de::DeserializeOwned;
 use serde_json::Value;
 
 use crate::data::{
-    Character, CharacterInfo, Class, ClassInfo, Experience, Faction, FireMode, FireModeInfo, Item,
-    Loadout, LocaleText, Vehicle, VehicleInfo,
+    Character, CharacterInfo, Class, ClassInfo, Environment, Experience, Faction, FireMode,
+    FireModeInfo, Item, Loadout, LocaleText, MetagameEventId, MetagameEventInfo, Vehicle,
+    VehicleInfo,
 };
-
-use serde::Deserialize;
+use crate::Error;
 
 #[derive(Clone)]
 pub struct CensusClient {
@@ -25,13 +26,15 @@ pub struct CensusClient {
     /// a cache for mapping character names to ids
     character_cache: HashMap<String, Character>,
     fire_mode_cache: HashMap<FireMode, FireModeInfo>,
+    metagame_event_cache: HashMap<MetagameEventId, MetagameEventInfo>,
 }
 impl CensusClient {
-    pub fn new(service_id: String) -> Self {
+    /// a client for the census namespace of the environment, so lookups match the event stream for the same environment
+    pub fn new(environment: Environment, service_id: String) -> Self {
         CensusClient {
             base_url: CENSUS_BASE_URL
-                .to_owned()
-                .replace("{service_id}", &service_id),
+                .replace("{service_id}", &service_id)
+                .replace("{namespace}", &environment.to_string()),
             client: Client::new(),
 
             vehicle_cache: HashMap::new(),
@@ -39,43 +42,100 @@ impl CensusClient {
             loadout_cache: HashMap::new(),
             character_cache: HashMap::new(),
             fire_mode_cache: HashMap::new(),
+            metagame_event_cache: HashMap::new(),
         }
     }
 
-    // performs a request to the api
-    async fn get(client: &mut Client, query: String) -> Result<Value, Box<dyn Error>> {
-        let resp = client.get(query).send().await?.text().await?;
-        match serde_json::from_str::<Value>(&resp) {
-            Ok(x) => Ok(x),
-            Err(e) => Err(Box::new(e)),
+    /// use a different base url for every request, like `http://localhost:8080/s:example/json/get/ps2:v2/`
+    ///
+    /// the collection name and query are added straight onto the end
+    pub fn with_base_url(mut self, base_url: String) -> Self {
+        self.base_url = base_url;
+        if !self.base_url.ends_with('/') {
+            self.base_url.push('/');
         }
+        self
+    }
+
+    /// make requests with an existing http client, to set up proxies, timeouts or a user agent
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    // performs a request to the api, and returns the last row in the list for the collection
+    async fn get<T: DeserializeOwned>(
+        client: &Client,
+        collection: &'static str,
+        id: &str,
+        query: String,
+    ) -> Result<T, Error> {
+        let http_err = |source| Error::Http {
+            collection,
+            id: id.to_owned(),
+            source,
+        };
+        let resp = client
+            .get(query)
+            .send()
+            .await
+            .map_err(http_err)?
+            .text()
+            .await
+            .map_err(http_err)?;
+        let mut resp = serde_json::from_str::<Value>(&resp)
+            .map_err(|e| Error::census_parse(collection, id, e))?;
+        let row = match resp[format!("{}_list", collection)].as_array_mut() {
+            Some(list) => list.pop().ok_or_else(|| Error::CensusNoRows {
+                collection,
+                id: id.to_owned(),
+            })?,
+            None => {
+                return Err(Error::census_parse(
+                    collection,
+                    id,
+                    format!("response has no {}_list", collection),
+                ))
+            }
+        };
+        serde_json::from_value::<T>(row).map_err(|e| Error::census_parse(collection, id, e))
+    }
+
+    /// gets a field from a row returned by the api as a string
+    fn str_field<'a>(
+        row: &'a Value,
+        path: &[&str],
+        collection: &'static str,
+        id: &str,
+    ) -> Result<&'a str, Error> {
+        path.iter()
+            .try_fold(row, |value, field| value.get(field))
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                Error::census_parse(collection, id, format!("missing field {}", path.join(".")))
+            })
     }
 
     /// fetches a character's id from their name
     pub async fn character_from_name(
         &mut self,
         character_name: String,
-    ) -> Result<Character, Box<dyn Error>> {
-        #[allow(dead_code)]
-        #[derive(Deserialize)]
-        struct CharacterContainer {
-            character_name_list: Vec<Value>,
-            returned: u32,
-        }
+    ) -> Result<Character, Error> {
+        const COLLECTION: &str = "character_name";
         let name = character_name.to_lowercase();
-        let err_str = "error parsing census character name response";
         match self.character_cache.get(&name) {
             Some(x) => Ok(*x),
             None => {
                 let url = format!("{}character_name?name.first_lower={}", self.base_url, name);
-                let resp = CensusClient::get(&mut self.client, url).await?;
-                let character = serde_json::from_value::<CharacterContainer>(resp)?
-                    .character_name_list
-                    .pop()
-                    .ok_or(err_str)?;
-                let id = Character {
-                    0: u64::from_str(character["character_id"].as_str().ok_or(err_str)?)?,
-                };
+                let character =
+                    CensusClient::get::<Value>(&self.client, COLLECTION, &name, url).await?;
+                let id = Character::from_str(CensusClient::str_field(
+                    &character,
+                    &["character_id"],
+                    COLLECTION,
+                    &name,
+                )?)
+                .map_err(|e| Error::census_parse(COLLECTION, &name, e))?;
                 self.character_cache.insert(name, id);
                 Ok(id)
             }
@@ -86,55 +146,34 @@ impl CensusClient {
     pub async fn character_info_from_id(
         &mut self,
         character_id: Character,
-    ) -> Result<CharacterInfo, Box<dyn Error>> {
-        #[allow(dead_code)]
-        #[derive(Deserialize)]
-        struct CharacterInfoContainer {
-            character_list: Vec<CharacterInfo>,
-            returned: u32,
-        }
-        let err_str = "error parsing census character response response";
-        let url = format!("{}character?character_id={}", self.base_url, character_id.0);
-        let resp = CensusClient::get(&mut self.client, url).await?;
-        let character_info = serde_json::from_value::<CharacterInfoContainer>(resp)?
-            .character_list
-            .pop()
-            .ok_or(err_str)?;
-        Ok(character_info)
+    ) -> Result<CharacterInfo, Error> {
+        let id = character_id.to_string();
+        let url = format!("{}character?character_id={}", self.base_url, id);
+        CensusClient::get(&self.client, "character", &id, url).await
     }
 
     /// fetches the weapon name, type and if it is on a vehicle from a fire mode id
     pub async fn fire_info_from_fire_mode(
         &mut self,
         fire_mode_id: FireMode,
-    ) -> Result<FireModeInfo, Box<dyn Error>> {
-        #[allow(dead_code)]
-        #[derive(Deserialize)]
-        struct FireModeContainer {
-            fire_mode_list: Vec<Value>,
-            returned: u32,
-        }
-        let err_str = "error parsing census fire mode response";
+    ) -> Result<FireModeInfo, Error> {
+        const COLLECTION: &str = "fire_mode";
         match self.fire_mode_cache.get(&fire_mode_id) {
             Some(x) => Ok(x.clone()),
             None => {
-                let url = format!("{}fire_mode?c:join=item^inject_at:item_info^show:name'is_vehicle_weapon&fire_mode_id={}", self.base_url, fire_mode_id.0);
-                let resp = CensusClient::get(&mut self.client, url).await?;
-                let info = serde_json::from_value::<FireModeContainer>(resp)?
-                    .fire_mode_list
-                    .pop()
-                    .ok_or(err_str)?;
+                let id = fire_mode_id.0.to_string();
+                let url = format!("{}fire_mode?c:join=item^inject_at:item_info^show:name'is_vehicle_weapon&fire_mode_id={}", self.base_url, id);
+                let info = CensusClient::get::<Value>(&self.client, COLLECTION, &id, url).await?;
+                let field = |path: &[&str]| CensusClient::str_field(&info, path, COLLECTION, &id);
                 let fire_info = FireModeInfo {
-                    item_id: Item::from_str(info["item_id"].as_str().ok_or(err_str)?)?,
-                    weapon_type: info["type"].as_str().ok_or(err_str)?.to_owned(),
+                    item_id: Item::from_str(field(&["item_id"])?)
+                        .map_err(|e| Error::census_parse(COLLECTION, &id, e))?,
+                    weapon_type: field(&["type"])?.to_owned(),
                     weapon_name: serde_json::from_value::<LocaleText>(
-                        info["item_info"].as_object().ok_or(err_str)?["name"].clone(),
-                    )?,
-                    weapon_is_vehicle_weapon: info["item_info"].as_object().ok_or(err_str)?
-                        ["is_vehicle_weapon"]
-                        .as_str()
-                        .ok_or(err_str)?
-                        != "0",
+                        info["item_info"]["name"].clone(),
+                    )
+                    .map_err(|e| Error::census_parse(COLLECTION, &id, e))?,
+                    weapon_is_vehicle_weapon: field(&["item_info", "is_vehicle_weapon"])? != "0",
                 };
                 self.fire_mode_cache.insert(fire_mode_id, fire_info.clone());
                 Ok(fire_info)
@@ -146,24 +185,14 @@ impl CensusClient {
     pub async fn vehicle_info_from_id(
         &mut self,
         vehicle_id: Vehicle,
-    ) -> Result<VehicleInfo, Box<dyn Error>> {
-        #[allow(dead_code)]
-        #[derive(Deserialize)]
-        struct VehicleInfoContainer {
-            vehicle_list: Vec<VehicleInfo>,
-            returned: u32,
-        }
-
-        let err_str = "error parsing census vehicle response";
+    ) -> Result<VehicleInfo, Error> {
         match self.vehicle_cache.get(&vehicle_id) {
             Some(x) => Ok(x.clone()),
             None => {
-                let url = format!("{}vehicle?vehicle_id={}", self.base_url, vehicle_id.0);
-                let resp = CensusClient::get(&mut self.client, url).await?;
-                let vehicle = serde_json::from_value::<VehicleInfoContainer>(resp)?
-                    .vehicle_list
-                    .pop()
-                    .ok_or(err_str)?;
+                let id = vehicle_id.0.to_string();
+                let url = format!("{}vehicle?vehicle_id={}", self.base_url, id);
+                let vehicle =
+                    CensusClient::get::<VehicleInfo>(&self.client, "vehicle", &id, url).await?;
                 self.vehicle_cache.insert(vehicle_id, vehicle.clone());
                 Ok(vehicle)
             }
@@ -174,27 +203,16 @@ impl CensusClient {
     pub async fn experience_name_from_id(
         &mut self,
         experience_id: Experience,
-    ) -> Result<String, Box<dyn Error>> {
-        #[allow(dead_code)]
-        #[derive(Deserialize)]
-        struct ExperienceContainer {
-            experience_list: Vec<Value>,
-            returned: u32,
-        }
-        let err_str = "error parsing census experience name";
+    ) -> Result<String, Error> {
+        const COLLECTION: &str = "experience";
         match self.experience_cache.get(&experience_id) {
             Some(x) => Ok(x.clone()),
             None => {
-                let url = format!(
-                    "{}experience?experience_id={}",
-                    self.base_url, experience_id.0
-                );
-                let resp = CensusClient::get(&mut self.client, url).await?;
-                let exp_val = serde_json::from_value::<ExperienceContainer>(resp)?
-                    .experience_list
-                    .pop()
-                    .ok_or(err_str)?;
-                let name = exp_val["description"].as_str().ok_or(err_str)?;
+                let id = experience_id.0.to_string();
+                let url = format!("{}experience?experience_id={}", self.base_url, id);
+                let exp_val =
+                    CensusClient::get::<Value>(&self.client, COLLECTION, &id, url).await?;
+                let name = CensusClient::str_field(&exp_val, &["description"], COLLECTION, &id)?;
                 self.experience_cache.insert(experience_id, name.to_owned());
                 Ok(name.to_owned())
             }
@@ -205,38 +223,74 @@ impl CensusClient {
     pub async fn class_info_from_loadout(
         &mut self,
         loadout_id: Loadout,
-    ) -> Result<ClassInfo, Box<dyn Error>> {
-        #[allow(dead_code)]
-        #[derive(Deserialize)]
-        struct LoadoutContainer {
-            loadout_list: Vec<Value>,
-            returned: u32,
-        }
-        let err_str = "error parsing census loadout response";
+    ) -> Result<ClassInfo, Error> {
+        const COLLECTION: &str = "loadout";
         match self.loadout_cache.get(&loadout_id) {
-            Some(x) => Ok(x.clone()),
+            Some(x) => Ok(*x),
             None => {
+                let id = loadout_id.0.to_string();
                 let url = format!(
                     "{}loadout?c:join=profile^inject_at:class^show:profile_type_id&loadout_id={}",
-                    self.base_url, loadout_id.0
+                    self.base_url, id
                 );
-                let resp = CensusClient::get(&mut self.client, url).await?;
-                let loadout = serde_json::from_value::<LoadoutContainer>(resp)?
-                    .loadout_list
-                    .pop()
-                    .ok_or(err_str)?;
+                let loadout =
+                    CensusClient::get::<Value>(&self.client, COLLECTION, &id, url).await?;
+                let field =
+                    |path: &[&str]| CensusClient::str_field(&loadout, path, COLLECTION, &id);
                 let player = ClassInfo {
-                    loadout_id: loadout_id,
-                    faction_id: Faction::from_str(loadout["faction_id"].as_str().ok_or(err_str)?)?,
-                    class: Class::from_str(
-                        loadout["class"].as_object().ok_or(err_str)?["profile_type_id"]
-                            .as_str()
-                            .ok_or(err_str)?,
-                    )?,
+                    loadout_id,
+                    faction_id: Faction::from_str(field(&["faction_id"])?)
+                        .map_err(|e| Error::census_parse(COLLECTION, &id, e))?,
+                    class: Class::from_str(field(&["class", "profile_type_id"])?)
+                        .map_err(|e| Error::census_parse(COLLECTION, &id, e))?,
                 };
                 self.loadout_cache.insert(loadout_id, player);
                 Ok(player)
             }
         }
     }
+
+    /// fetches the name, description and duration of a metagame event (alert) from it's id
+    ///
+    /// if the census can't be reached, common alerts are still answered from a built-in table, without the description and type
+    pub async fn metagame_event_info_from_id(
+        &mut self,
+        metagame_event_id: MetagameEventId,
+    ) -> Result<MetagameEventInfo, Error> {
+        match self.metagame_event_cache.get(&metagame_event_id) {
+            Some(x) => Ok(x.clone()),
+            None => match self.fetch_metagame_event_info(metagame_event_id).await {
+                Ok(info) => {
+                    self.metagame_event_cache
+                        .insert(metagame_event_id, info.clone());
+                    Ok(info)
+                }
+                Err(e) => metagame_event_id.builtin_info().ok_or(e),
+            },
+        }
+    }
+
+    async fn fetch_metagame_event_info(
+        &self,
+        metagame_event_id: MetagameEventId,
+    ) -> Result<MetagameEventInfo, Error> {
+        const COLLECTION: &str = "metagame_event";
+        let id = metagame_event_id.0.to_string();
+        let url = format!("{}metagame_event?metagame_event_id={}", self.base_url, id);
+        let event = CensusClient::get::<Value>(&self.client, COLLECTION, &id, url).await?;
+        let field = |path: &[&str]| CensusClient::str_field(&event, path, COLLECTION, &id);
+        Ok(MetagameEventInfo {
+            id: metagame_event_id,
+            name: field(&["name", "en"])?.to_owned(),
+            description: Some(field(&["description", "en"])?.to_owned()),
+            type_id: Some(
+                u32::from_str(field(&["type"])?)
+                    .map_err(|e| Error::census_parse(COLLECTION, &id, e))?,
+            ),
+            duration_minutes: u32::from_str(field(&["duration_minutes"])?)
+                .map_err(|e| Error::census_parse(COLLECTION, &id, e))?,
+            // the census doesn't say which continent an event is on
+            zone: metagame_event_id.builtin_info().and_then(|info| info.zone),
+        })
+    }
 }
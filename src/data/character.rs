@@ -10,7 +10,7 @@ use crate::{
     data::{BattleRank, Faction, Timestamp},
 };
 
-#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+#[derive(Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 pub struct Character(pub u64);
 impl FromStr for Character {
     type Err = ParseIntError;
@@ -16,11 +16,8 @@ impl FromStr for Experience {
 }
 impl Experience {
     pub async fn info(&self, client: &mut CensusClient) -> Option<ExperienceInfo> {
-        if let Some(name) = client.experience_name_from_id(*self).await.ok() {
-            Some(ExperienceInfo {
-                id: *self,
-                name: name,
-            })
+        if let Ok(name) = client.experience_name_from_id(*self).await {
+            Some(ExperienceInfo { id: *self, name })
         } else {
             None
         }
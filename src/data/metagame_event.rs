@@ -0,0 +1,163 @@
+use num_enum::TryFromPrimitive;
+use serde::Deserialize;
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use std::fmt;
+use std::num::ParseIntError;
+use std::str::FromStr;
+
+use crate::census::CensusClient;
+use crate::data::{parse_id, ParseIdError, Zone};
+
+/// the id of a kind of metagame event (alert), like an Indar superiority alert
+#[derive(Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct MetagameEventId(pub u32);
+impl FromStr for MetagameEventId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<MetagameEventId, Self::Err> {
+        Ok(MetagameEventId(u32::from_str(s)?))
+    }
+}
+impl MetagameEventId {
+    pub async fn info(&self, client: &mut CensusClient) -> Option<MetagameEventInfo> {
+        client.metagame_event_info_from_id(*self).await.ok()
+    }
+
+    /// looks the event up in the built-in table of common alerts, without going to the census
+    pub fn builtin_info(&self) -> Option<MetagameEventInfo> {
+        BUILTIN_METAGAME_EVENTS
+            .iter()
+            .find(|(id, ..)| *id == self.0)
+            .map(|&(id, name, zone, duration_minutes)| MetagameEventInfo {
+                id: MetagameEventId(id),
+                name: name.to_owned(),
+                description: None,
+                type_id: None,
+                duration_minutes,
+                zone: Some(zone),
+            })
+    }
+}
+
+/// the alerts that come up all the time, as (id, name, zone, duration in minutes)
+///
+/// each continent has one alert per faction, in NC (Liberation), TR (Superiority), VS (Enlightenment) order
+const BUILTIN_METAGAME_EVENTS: &[(u32, &str, Zone, u32)] = &[
+    (147, "Indar Liberation", Zone::Indar, 90),
+    (148, "Indar Superiority", Zone::Indar, 90),
+    (149, "Indar Enlightenment", Zone::Indar, 90),
+    (150, "Esamir Liberation", Zone::Esamir, 90),
+    (151, "Esamir Superiority", Zone::Esamir, 90),
+    (152, "Esamir Enlightenment", Zone::Esamir, 90),
+    (153, "Hossin Liberation", Zone::Hossin, 90),
+    (154, "Hossin Superiority", Zone::Hossin, 90),
+    (155, "Hossin Enlightenment", Zone::Hossin, 90),
+    (156, "Amerish Liberation", Zone::Amerish, 90),
+    (157, "Amerish Superiority", Zone::Amerish, 90),
+    (158, "Amerish Enlightenment", Zone::Amerish, 90),
+    (248, "Oshur Liberation", Zone::Oshur, 90),
+    (249, "Oshur Superiority", Zone::Oshur, 90),
+    (250, "Oshur Enlightenment", Zone::Oshur, 90),
+];
+
+/// a kind of metagame event, from the built-in table or the census `metagame_event` collection
+#[derive(Debug, Clone)]
+pub struct MetagameEventInfo {
+    pub id: MetagameEventId,
+    /// the english name, like "Indar Superiority"
+    pub name: String,
+    /// the english description, only known when the event came from the census
+    pub description: Option<String>,
+    /// the census `type` of the event, only known when the event came from the census
+    pub type_id: Option<u32>,
+    pub duration_minutes: u32,
+    /// the continent the event runs on, only known for events in the built-in table, even when the rest came from the census
+    pub zone: Option<Zone>,
+}
+impl fmt::Display for MetagameEventInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} min)", self.name, self.duration_minutes)
+    }
+}
+
+/// what happened to a metagame event, sent as `metagame_event_state`
+#[derive(
+    Serialize_repr,
+    Deserialize_repr,
+    TryFromPrimitive,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Clone,
+    Copy,
+)]
+#[repr(u32)]
+pub enum MetagameEventState {
+    Started = 135,
+    Restarted = 136,
+    Cancelled = 137,
+    Ended = 138,
+    XpBonusChanged = 139,
+}
+impl FromStr for MetagameEventState {
+    type Err = ParseIdError<MetagameEventState>;
+
+    fn from_str(s: &str) -> Result<MetagameEventState, Self::Err> {
+        parse_id(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_alerts() {
+        let info = MetagameEventId(147).builtin_info().unwrap();
+        assert_eq!(info.to_string(), "Indar Liberation (90 min)");
+        assert_eq!(info.zone, Some(Zone::Indar));
+        assert_eq!(
+            MetagameEventId(151).builtin_info().unwrap().name,
+            "Esamir Superiority"
+        );
+        assert_eq!(
+            MetagameEventId(250).builtin_info().unwrap().name,
+            "Oshur Enlightenment"
+        );
+        assert!(MetagameEventId(1).builtin_info().is_none());
+    }
+
+    #[test]
+    fn builtin_alerts_are_in_faction_order() {
+        for (id, name, zone, _) in BUILTIN_METAGAME_EVENTS {
+            let first = if *zone == Zone::Oshur { 248 } else { 147 };
+            let suffix =
+                ["Liberation", "Superiority", "Enlightenment"][((id - first) % 3) as usize];
+            assert!(name.ends_with(suffix), "{} {}", id, name);
+        }
+    }
+
+    #[test]
+    fn metagame_event_states() {
+        assert_eq!(
+            MetagameEventState::from_str("135"),
+            Ok(MetagameEventState::Started)
+        );
+        assert_eq!(
+            MetagameEventState::from_str("138"),
+            Ok(MetagameEventState::Ended)
+        );
+        assert!(matches!(
+            MetagameEventState::from_str("abc"),
+            Err(ParseIdError::NotANumber(_))
+        ));
+        assert!(matches!(
+            MetagameEventState::from_str("0"),
+            Err(ParseIdError::Unknown(e)) if e.number == 0
+        ));
+    }
+}
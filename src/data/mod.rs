@@ -1,5 +1,5 @@
-use serde::{Deserialize, Serialize};
-use serde_repr::Serialize_repr;
+use serde::de::{self, Unexpected, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use num_enum::{TryFromPrimitive, TryFromPrimitiveError};
 use std::num::ParseIntError;
@@ -17,6 +17,43 @@ mod fire_mode;
 pub use fire_mode::*;
 mod experience;
 pub use experience::*;
+mod metagame_event;
+pub use metagame_event::*;
+
+/// why an id couldn't be turned into one of the id enums, like [`Faction`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseIdError<T: TryFromPrimitive> {
+    /// the id wasn't a number at all
+    NotANumber(ParseIntError),
+    /// the id was a number, but not one the enum has
+    Unknown(TryFromPrimitiveError<T>),
+}
+impl<T: TryFromPrimitive> fmt::Display for ParseIdError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseIdError::NotANumber(e) => write!(f, "{} id is not a number: {}", T::NAME, e),
+            ParseIdError::Unknown(e) => write!(f, "{}", e),
+        }
+    }
+}
+impl<T: TryFromPrimitive + fmt::Debug> std::error::Error for ParseIdError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseIdError::NotANumber(e) => Some(e),
+            ParseIdError::Unknown(_) => None,
+        }
+    }
+}
+
+/// parses an id sent as a string into one of the id enums
+pub(crate) fn parse_id<T>(s: &str) -> Result<T, ParseIdError<T>>
+where
+    T: TryFromPrimitive<Error = TryFromPrimitiveError<T>>,
+    T::Primitive: FromStr<Err = ParseIntError>,
+{
+    let val = T::Primitive::from_str(s).map_err(ParseIdError::NotANumber)?;
+    T::try_from_primitive(val).map_err(ParseIdError::Unknown)
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct LocaleText {
@@ -28,12 +65,22 @@ pub struct LocaleText {
     tr: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Environment {
     PC,
     Ps4US,
     Ps4EU,
 }
+impl Environment {
+    /// every world the crate knows about in the environment
+    pub fn worlds(&self) -> &'static [World] {
+        match self {
+            Environment::PC => World::ALL_WORLDS,
+            Environment::Ps4US => World::PS4_US_WORLDS,
+            Environment::Ps4EU => World::PS4_EU_WORLDS,
+        }
+    }
+}
 impl fmt::Display for Environment {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -44,28 +91,152 @@ impl fmt::Display for Environment {
     }
 }
 
-#[derive(Serialize_repr, Deserialize, TryFromPrimitive, Debug, PartialEq, Eq, Clone, Copy)]
-#[repr(u32)]
+/// a server, sent as its numerical id, or `"all"` in subscriptions
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 pub enum World {
-    // All, // this should select all servers, but didn't work in testing
-    Connery = 1,
-    Miller = 10,
-    Cobalt = 13,
-    Emerald = 17,
-    Jaeger = 19,
-    Apex = 24,
-    Briggs = 25,
-    SolTech = 40,
+    /// every world, only makes sense when subscribing
+    All,
+    Connery,
+    Miller,
+    Cobalt,
+    Emerald,
+    Jaeger,
+    Apex,
+    Briggs,
+    SolTech,
+    Genudine,
+    Palos,
+    Crux,
+    Searhus,
+    Xelas,
+    Ceres,
+    Lithcorp,
+    Rashnu,
+    /// a world the crate doesn't know about, like a new or temporary server
+    Other(u32),
+}
+impl From<u32> for World {
+    fn from(id: u32) -> World {
+        match id {
+            1 => World::Connery,
+            10 => World::Miller,
+            13 => World::Cobalt,
+            17 => World::Emerald,
+            19 => World::Jaeger,
+            24 => World::Apex,
+            25 => World::Briggs,
+            40 => World::SolTech,
+            1000 => World::Genudine,
+            1001 => World::Palos,
+            1002 => World::Crux,
+            1003 => World::Searhus,
+            1004 => World::Xelas,
+            2000 => World::Ceres,
+            2001 => World::Lithcorp,
+            2002 => World::Rashnu,
+            id => World::Other(id),
+        }
+    }
 }
 impl FromStr for World {
-    type Err = TryFromPrimitiveError<World>;
+    type Err = ParseIntError;
 
     fn from_str(s: &str) -> Result<World, Self::Err> {
-        let val = u32::from_str(s).expect("tried to deserialize non-numeric world id");
-        World::try_from(val)
+        if s == "all" {
+            return Ok(World::All);
+        }
+        Ok(World::from(u32::from_str(s)?))
+    }
+}
+impl Serialize for World {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.id() {
+            Some(id) => serializer.serialize_u32(id),
+            None => serializer.serialize_str("all"),
+        }
+    }
+}
+impl<'de> Deserialize<'de> for World {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct WorldVisitor;
+        impl Visitor<'_> for WorldVisitor {
+            type Value = World;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a world id, as a number or a string, or \"all\"")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<World, E> {
+                u32::try_from(v)
+                    .map(World::from)
+                    .map_err(|_| E::invalid_value(Unexpected::Unsigned(v), &self))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<World, E> {
+                u32::try_from(v)
+                    .map(World::from)
+                    .map_err(|_| E::invalid_value(Unexpected::Signed(v), &self))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<World, E> {
+                World::from_str(v).map_err(|_| E::invalid_value(Unexpected::Str(v), &self))
+            }
+        }
+
+        deserializer.deserialize_any(WorldVisitor)
     }
 }
 impl World {
+    /// the numerical id of the world, `None` for [`World::All`]
+    pub fn id(&self) -> Option<u32> {
+        Some(match self {
+            World::All => return None,
+            World::Connery => 1,
+            World::Miller => 10,
+            World::Cobalt => 13,
+            World::Emerald => 17,
+            World::Jaeger => 19,
+            World::Apex => 24,
+            World::Briggs => 25,
+            World::SolTech => 40,
+            World::Genudine => 1000,
+            World::Palos => 1001,
+            World::Crux => 1002,
+            World::Searhus => 1003,
+            World::Xelas => 1004,
+            World::Ceres => 2000,
+            World::Lithcorp => 2001,
+            World::Rashnu => 2002,
+            World::Other(id) => *id,
+        })
+    }
+
+    /// the environment the world is part of, worlds the crate doesn't know about are assumed to be PC worlds
+    pub fn environment(&self) -> Environment {
+        match self {
+            World::Genudine | World::Palos | World::Crux | World::Searhus | World::Xelas => {
+                Environment::Ps4US
+            }
+            World::Ceres | World::Lithcorp | World::Rashnu => Environment::Ps4EU,
+            _ => Environment::PC,
+        }
+    }
+
+    /// if the world can be subscribed to in the environment, [`World::All`] and worlds the crate doesn't know about always can
+    pub fn is_in(&self, environment: Environment) -> bool {
+        match self {
+            World::All | World::Other(_) => true,
+            world => world.environment() == environment,
+        }
+    }
+
+    /// every PC world
     pub const ALL_WORLDS: &'static [World] = &[
         World::Apex,
         World::Briggs,
@@ -76,9 +247,29 @@ impl World {
         World::Miller,
         World::SolTech,
     ];
+    pub const PS4_US_WORLDS: &'static [World] = &[
+        World::Crux,
+        World::Genudine,
+        World::Palos,
+        World::Searhus,
+        World::Xelas,
+    ];
+    pub const PS4_EU_WORLDS: &'static [World] = &[World::Ceres, World::Lithcorp, World::Rashnu];
 }
 
-#[derive(Serialize, Deserialize, TryFromPrimitive, Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(
+    Serialize,
+    Deserialize,
+    TryFromPrimitive,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Clone,
+    Copy,
+)]
 #[repr(u32)]
 pub enum Zone {
     Indar = 2,
@@ -89,10 +280,10 @@ pub enum Zone {
     VRTrainingNC = 96,
     VRTrainingTR = 97,
     VRTrainingVS = 98,
-    Koltyr = 10000,
-    Desolation = 20000,
-    // Nexus
-    Sanctuary = 131434,
+    Nexus = 10,
+    Koltyr = 14,
+    Desolation = 361,
+    Sanctuary = 362,
 }
 impl FromStr for Zone {
     type Err = TryFromPrimitiveError<Zone>;
@@ -106,7 +297,69 @@ impl FromStr for Zone {
     }
 }
 
-#[derive(Serialize, Deserialize, TryFromPrimitive, Debug, PartialEq, Eq, Clone, Copy)]
+/// a zone id as sent in events, which for instanced zones like outfit wars, Koltyr and Desolation packs the instance number in with the zone
+///
+/// the low 16 bits are the zone definition and the high bits are the instance, so `131434` is instance 2 of Sanctuary (`362`)
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct ZoneInstance {
+    /// the id of the zone definition, which may not be one the crate knows about
+    pub definition_id: u16,
+    /// the instance of the zone, `0` for the normal continents
+    pub instance: u16,
+}
+impl ZoneInstance {
+    /// the zone definition, `None` if the crate doesn't know about it
+    pub fn zone(&self) -> Option<Zone> {
+        Zone::try_from(self.definition_id as u32).ok()
+    }
+
+    /// the packed id, as the server sends it
+    pub fn id(&self) -> u32 {
+        ((self.instance as u32) << 16) | self.definition_id as u32
+    }
+}
+impl From<u32> for ZoneInstance {
+    fn from(id: u32) -> ZoneInstance {
+        ZoneInstance {
+            definition_id: (id & 0xFFFF) as u16,
+            instance: (id >> 16) as u16,
+        }
+    }
+}
+impl From<Zone> for ZoneInstance {
+    fn from(zone: Zone) -> ZoneInstance {
+        ZoneInstance::from(zone as u32)
+    }
+}
+impl FromStr for ZoneInstance {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<ZoneInstance, Self::Err> {
+        Ok(ZoneInstance::from(u32::from_str(s)?))
+    }
+}
+impl<'de> Deserialize<'de> for ZoneInstance {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        u32::deserialize(deserializer).map(ZoneInstance::from)
+    }
+}
+
+#[derive(
+    Serialize,
+    Deserialize,
+    TryFromPrimitive,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Clone,
+    Copy,
+)]
 #[repr(u8)]
 pub enum Faction {
     None = 0,
@@ -116,11 +369,10 @@ pub enum Faction {
     NSOperatives = 4,
 }
 impl FromStr for Faction {
-    type Err = TryFromPrimitiveError<Faction>;
+    type Err = ParseIdError<Faction>;
 
     fn from_str(s: &str) -> Result<Faction, Self::Err> {
-        let val = u8::from_str(s).expect("tried to deserialize non-numeric faction id");
-        Faction::try_from(val)
+        parse_id(s)
     }
 }
 
@@ -135,11 +387,10 @@ pub enum Class {
     MAX = 7,
 }
 impl FromStr for Class {
-    type Err = TryFromPrimitiveError<Class>;
+    type Err = ParseIdError<Class>;
 
     fn from_str(s: &str) -> Result<Class, Self::Err> {
-        let val = u8::from_str(s).expect("tried to deserialize non-numeric class id");
-        Class::try_from(val)
+        parse_id(s)
     }
 }
 
@@ -220,3 +471,111 @@ impl FromStr for Outfit {
         Ok(Outfit(u64::from_str(s)?))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faction_ids() {
+        assert_eq!(Faction::from_str("0"), Ok(Faction::None));
+        assert_eq!(Faction::from_str("3"), Ok(Faction::TerranRepublic));
+        assert!(matches!(
+            Faction::from_str(""),
+            Err(ParseIdError::NotANumber(_))
+        ));
+        assert!(matches!(
+            Faction::from_str("9"),
+            Err(ParseIdError::Unknown(TryFromPrimitiveError { number: 9 }))
+        ));
+    }
+
+    #[test]
+    fn class_ids() {
+        assert_eq!(Class::from_str("7"), Ok(Class::MAX));
+        assert!(matches!(
+            Class::from_str("abc"),
+            Err(ParseIdError::NotANumber(_))
+        ));
+        assert!(matches!(
+            Class::from_str("2"),
+            Err(ParseIdError::Unknown(TryFromPrimitiveError { number: 2 }))
+        ));
+    }
+
+    #[test]
+    fn world_from_every_form() {
+        assert_eq!(
+            serde_json::from_str::<World>(r#""17""#).unwrap(),
+            World::Emerald
+        );
+        assert_eq!(serde_json::from_str::<World>("17").unwrap(), World::Emerald);
+        assert_eq!(
+            serde_json::from_str::<World>(r#""all""#).unwrap(),
+            World::All
+        );
+        assert_eq!(
+            serde_json::from_str::<World>(r#""9999""#).unwrap(),
+            World::Other(9999)
+        );
+        assert_eq!(
+            serde_json::from_str::<World>("9999").unwrap(),
+            World::Other(9999)
+        );
+        assert!(serde_json::from_str::<World>(r#""Emerald""#).is_err());
+        assert!(serde_json::from_str::<World>("-1").is_err());
+    }
+
+    #[test]
+    fn world_round_trips() {
+        assert_eq!(serde_json::to_string(&World::All).unwrap(), r#""all""#);
+        assert_eq!(serde_json::to_string(&World::Emerald).unwrap(), "17");
+        assert_eq!(serde_json::to_string(&World::Other(9999)).unwrap(), "9999");
+        for world in World::ALL_WORLDS
+            .iter()
+            .chain(World::PS4_US_WORLDS)
+            .chain(World::PS4_EU_WORLDS)
+        {
+            let id = world.id().unwrap();
+            assert_eq!(World::from(id), *world);
+            assert_eq!(World::from_str(&id.to_string()), Ok(*world));
+        }
+    }
+
+    #[test]
+    fn zone_instance_splits_packed_ids() {
+        let sanctuary = ZoneInstance::from(131434);
+        assert_eq!(sanctuary.definition_id, 362);
+        assert_eq!(sanctuary.instance, 2);
+        assert_eq!(sanctuary.zone(), Some(Zone::Sanctuary));
+        assert_eq!(sanctuary.id(), 131434);
+
+        let unknown = ZoneInstance::from((7 << 16) | 999);
+        assert_eq!(unknown.zone(), None);
+        assert_eq!(unknown.instance, 7);
+        assert_eq!(unknown.id(), (7 << 16) | 999);
+    }
+
+    #[test]
+    fn zone_instance_for_continents() {
+        let indar = ZoneInstance::from_str("2").unwrap();
+        assert_eq!(indar.instance, 0);
+        assert_eq!(indar.zone(), Some(Zone::Indar));
+        assert_eq!(ZoneInstance::from(Zone::Indar), indar);
+        assert_eq!(serde_json::from_str::<ZoneInstance>("2").unwrap(), indar);
+    }
+
+    #[test]
+    fn zone_definition_ids() {
+        for (id, zone) in [
+            (10, Zone::Nexus),
+            (14, Zone::Koltyr),
+            (344, Zone::Oshur),
+            (361, Zone::Desolation),
+            (362, Zone::Sanctuary),
+        ] {
+            assert_eq!(Zone::try_from(id), Ok(zone));
+            assert_eq!(ZoneInstance::from(zone).id(), id);
+        }
+    }
+}
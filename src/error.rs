@@ -0,0 +1,148 @@
+use std::fmt;
+
+use tokio_tungstenite::tungstenite;
+
+use crate::data::{Environment, World};
+
+/// a boxed error that can be sent between tasks, used as the source of errors from unexpected census data
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// everything that can go wrong in the event and census clients
+#[derive(Debug)]
+pub enum Error {
+    /// the event client tried to talk to the server before `connect` was called
+    NotConnected,
+    /// the connection to the server closed before it replied to a request
+    ConnectionClosed,
+    /// the server sent nothing for longer than the idle timeout, so the connection was dropped
+    Stale { idle: std::time::Duration },
+    /// the server didn't reply to a request within the reply timeout
+    ReplyTimeout(std::time::Duration),
+    /// a request named a world that isn't part of the environment the client is connected to
+    WrongEnvironment {
+        world: World,
+        environment: Environment,
+    },
+    /// a websocket or census url could not be parsed
+    InvalidUrl(url::ParseError),
+    /// the websocket connection failed, or returned an error
+    WebSocket(Box<tungstenite::Error>),
+    /// a request could not be serialized
+    Json(serde_json::Error),
+    /// part of a websocket message from the server was not valid JSON, `message` holds the bytes from where it went wrong
+    MalformedMessage {
+        message: Vec<u8>,
+        source: serde_json::Error,
+    },
+
+    /// the http request to the census api failed
+    Http {
+        collection: &'static str,
+        id: String,
+        source: reqwest::Error,
+    },
+    /// the census api returned no rows for the query
+    CensusNoRows {
+        collection: &'static str,
+        id: String,
+    },
+    /// the census api returned a row that doesn't have the shape this crate expects
+    CensusParse {
+        collection: &'static str,
+        id: String,
+        source: BoxError,
+    },
+}
+impl Error {
+    pub(crate) fn census_parse(
+        collection: &'static str,
+        id: impl ToString,
+        source: impl Into<BoxError>,
+    ) -> Self {
+        Error::CensusParse {
+            collection,
+            id: id.to_string(),
+            source: source.into(),
+        }
+    }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotConnected => write!(
+                f,
+                "planetside2 event client not connected to websocket, make sure to call 'connect' first"
+            ),
+            Error::ConnectionClosed => write!(
+                f,
+                "connection to the planetside2 event server closed before it replied"
+            ),
+            Error::Stale { idle } => write!(
+                f,
+                "connection to the planetside2 event server has been quiet for {:?}",
+                idle
+            ),
+            Error::ReplyTimeout(waited) => write!(
+                f,
+                "the planetside2 event server didn't reply within {:?}",
+                waited
+            ),
+            Error::WrongEnvironment { world, environment } => write!(
+                f,
+                "world {:?} is not part of the {:?} environment",
+                world, environment
+            ),
+            Error::InvalidUrl(e) => write!(f, "invalid url: {}", e),
+            Error::WebSocket(e) => write!(f, "websocket error: {}", e),
+            Error::Json(e) => write!(f, "json error: {}", e),
+            Error::MalformedMessage { message, source } => write!(
+                f,
+                "malformed message from server ({}): {}",
+                source,
+                String::from_utf8_lossy(message)
+            ),
+            Error::Http { collection, id, .. } => {
+                write!(f, "census request for {} {} failed", collection, id)
+            }
+            Error::CensusNoRows { collection, id } => {
+                write!(f, "census returned no {} for {}", collection, id)
+            }
+            Error::CensusParse { collection, id, .. } => {
+                write!(f, "error parsing census {} response for {}", collection, id)
+            }
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::NotConnected
+            | Error::ConnectionClosed
+            | Error::Stale { .. }
+            | Error::ReplyTimeout(_)
+            | Error::WrongEnvironment { .. }
+            | Error::CensusNoRows { .. } => None,
+            Error::InvalidUrl(e) => Some(e),
+            Error::WebSocket(e) => Some(e.as_ref()),
+            Error::Json(e) => Some(e),
+            Error::MalformedMessage { source, .. } => Some(source),
+            Error::Http { source, .. } => Some(source),
+            Error::CensusParse { source, .. } => Some(source.as_ref()),
+        }
+    }
+}
+impl From<url::ParseError> for Error {
+    fn from(e: url::ParseError) -> Self {
+        Error::InvalidUrl(e)
+    }
+}
+impl From<tungstenite::Error> for Error {
+    fn from(e: tungstenite::Error) -> Self {
+        Error::WebSocket(Box::new(e))
+    }
+}
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
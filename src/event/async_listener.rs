@@ -0,0 +1,133 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::task::{Context, Poll};
+
+use futures_util::future::BoxFuture;
+use futures_util::stream::FuturesUnordered;
+use futures_util::{FutureExt, StreamExt};
+
+use crate::data::Character;
+
+use super::EventPayload;
+
+type AsyncEventCallback = Box<dyn FnMut(EventPayload) -> BoxFuture<'static, ()> + Send>;
+
+/// how many futures from one async listener are run at the same time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsyncConcurrency {
+    /// one event at a time, in the order they arrived
+    Sequential,
+    /// up to this many events at a time, in no particular order, a limit of 0 is treated as 1
+    Bounded(usize),
+    /// events for the same character are handled one at a time in the order they arrived, while different characters are handled at the same time
+    ///
+    /// events are grouped by the character they happened to, so the victim of a death, and events with no character are all handled in order together
+    PerCharacter,
+}
+
+/// an async listener, along with the events waiting for it and the futures it is running
+pub(crate) struct AsyncEventListener {
+    callback: AsyncEventCallback,
+    concurrency: AsyncConcurrency,
+
+    /// events waiting to be handled, grouped by character for `PerCharacter`, and all under `None` otherwise
+    queued: HashMap<Option<Character>, VecDeque<EventPayload>>,
+    /// the groups that have a future running, only checked for `PerCharacter`
+    busy: HashSet<Option<Character>>,
+    /// each running future resolves to the group it was for
+    running: FuturesUnordered<BoxFuture<'static, Option<Character>>>,
+}
+impl AsyncEventListener {
+    pub fn new<F, Fut>(concurrency: AsyncConcurrency, mut callback: F) -> Self
+    where
+        F: 'static + Send + FnMut(EventPayload) -> Fut,
+        Fut: 'static + Send + Future<Output = ()>,
+    {
+        AsyncEventListener {
+            callback: Box::new(move |payload| callback(payload).boxed()),
+            concurrency,
+            queued: HashMap::new(),
+            busy: HashSet::new(),
+            running: FuturesUnordered::new(),
+        }
+    }
+
+    /// if nothing is running, queued events are always waiting on something that is running
+    pub fn is_idle(&self) -> bool {
+        self.running.is_empty()
+    }
+
+    /// queue an event, starting the listener's future for it straight away if the concurrency policy allows
+    pub fn push(&mut self, payload: EventPayload) {
+        let group = match self.concurrency {
+            AsyncConcurrency::PerCharacter => payload.characters().next(),
+            _ => None,
+        };
+        self.queued.entry(group).or_default().push_back(payload);
+        self.start_ready();
+    }
+
+    /// poll the running futures, returning `Ready` once at least one has finished
+    pub fn poll_finished(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let mut finished = false;
+        // new futures started as others finish need polling too, so keep going until nothing is ready
+        while let Poll::Ready(Some(group)) = self.running.poll_next_unpin(cx) {
+            self.busy.remove(&group);
+            self.start_ready();
+            finished = true;
+        }
+        if finished {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn start_ready(&mut self) {
+        match self.concurrency {
+            AsyncConcurrency::Sequential => self.start_up_to(1),
+            AsyncConcurrency::Bounded(limit) => self.start_up_to(limit.max(1)),
+            AsyncConcurrency::PerCharacter => {
+                let ready: Vec<_> = self
+                    .queued
+                    .keys()
+                    .filter(|group| !self.busy.contains(*group))
+                    .copied()
+                    .collect();
+                for group in ready {
+                    self.start(group);
+                }
+            }
+        }
+    }
+
+    fn start_up_to(&mut self, limit: usize) {
+        while self.running.len() < limit && self.queued.contains_key(&None) {
+            self.start(None);
+        }
+    }
+
+    /// start the future for the next event in a group
+    fn start(&mut self, group: Option<Character>) {
+        let queue = match self.queued.get_mut(&group) {
+            Some(queue) => queue,
+            None => return,
+        };
+        let payload = match queue.pop_front() {
+            Some(payload) => payload,
+            None => return,
+        };
+        if queue.is_empty() {
+            self.queued.remove(&group);
+        }
+        let future = (self.callback)(payload);
+        self.busy.insert(group);
+        self.running.push(
+            async move {
+                future.await;
+                group
+            }
+            .boxed(),
+        );
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::BTreeSet;
+
+use crate::data::{Character, Experience, Faction, World, Zone};
+
+use super::EventPayload;
+
+/// decides which events reach a listener, see [`CallbackHolder::register_filtered_event_listener`](super::CallbackHolder::register_filtered_event_listener)
+///
+/// filters can be combined with [`and`](EventFilter::and) and [`or`](EventFilter::or), and inverted with `!`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventFilter {
+    /// events on any of the worlds
+    Worlds(BTreeSet<World>),
+    /// events in any of the zones, logins and logouts never match as they have no zone
+    Zones(BTreeSet<Zone>),
+    /// events involving any of the characters, as either the attacker or the victim
+    Characters(BTreeSet<Character>),
+    /// events naming any of the factions, see [`EventPayload::factions`]
+    Factions(BTreeSet<Faction>),
+    /// experience events for any of the experience ids
+    Experience(BTreeSet<Experience>),
+    /// events matching every filter, matches everything if empty
+    All(Vec<EventFilter>),
+    /// events matching at least one filter, matches nothing if empty
+    Any(Vec<EventFilter>),
+    /// events not matching the filter
+    Not(Box<EventFilter>),
+}
+impl EventFilter {
+    pub fn worlds(worlds: impl IntoIterator<Item = World>) -> Self {
+        EventFilter::Worlds(worlds.into_iter().collect())
+    }
+    pub fn zones(zones: impl IntoIterator<Item = Zone>) -> Self {
+        EventFilter::Zones(zones.into_iter().collect())
+    }
+    pub fn characters(characters: impl IntoIterator<Item = Character>) -> Self {
+        EventFilter::Characters(characters.into_iter().collect())
+    }
+    pub fn factions(factions: impl IntoIterator<Item = Faction>) -> Self {
+        EventFilter::Factions(factions.into_iter().collect())
+    }
+    pub fn experience(experience: impl IntoIterator<Item = Experience>) -> Self {
+        EventFilter::Experience(experience.into_iter().collect())
+    }
+
+    /// only events matching both filters
+    pub fn and(self, other: EventFilter) -> Self {
+        match self {
+            EventFilter::All(mut filters) => {
+                filters.push(other);
+                EventFilter::All(filters)
+            }
+            filter => EventFilter::All(vec![filter, other]),
+        }
+    }
+    /// events matching either filter
+    pub fn or(self, other: EventFilter) -> Self {
+        match self {
+            EventFilter::Any(mut filters) => {
+                filters.push(other);
+                EventFilter::Any(filters)
+            }
+            filter => EventFilter::Any(vec![filter, other]),
+        }
+    }
+
+    pub fn matches(&self, event: &EventPayload) -> bool {
+        match self {
+            EventFilter::Worlds(worlds) => event.world().is_some_and(|w| worlds.contains(&w)),
+            EventFilter::Zones(zones) => event.zone().is_some_and(|z| zones.contains(&z)),
+            EventFilter::Characters(characters) => {
+                event.characters().any(|c| characters.contains(&c))
+            }
+            EventFilter::Factions(factions) => event.factions().any(|f| factions.contains(&f)),
+            EventFilter::Experience(experience) => {
+                event.experience().is_some_and(|e| experience.contains(&e))
+            }
+            EventFilter::All(filters) => filters.iter().all(|f| f.matches(event)),
+            EventFilter::Any(filters) => filters.iter().any(|f| f.matches(event)),
+            EventFilter::Not(filter) => !filter.matches(event),
+        }
+    }
+}
+impl std::ops::Not for EventFilter {
+    type Output = EventFilter;
+
+    fn not(self) -> Self::Output {
+        EventFilter::Not(Box::new(self))
+    }
+}
@@ -0,0 +1,100 @@
+use serde::de::IgnoredAny;
+use serde_json::Deserializer;
+
+/// the rest of a websocket message from the point where it stopped being valid JSON
+pub(crate) struct MalformedFrame<'a> {
+    pub bytes: &'a [u8],
+    pub error: serde_json::Error,
+}
+
+/// splits a websocket message into each of the JSON documents in it, as the server sometimes sticks several messages together
+///
+/// once part of the message is not valid JSON the rest of it is returned as a single malformed frame, as there is no reliable way to find where the next document starts
+pub(crate) fn split_frames(
+    message: &[u8],
+) -> impl Iterator<Item = Result<&[u8], MalformedFrame<'_>>> {
+    let mut documents = Deserializer::from_slice(message).into_iter::<IgnoredAny>();
+    let mut start = 0;
+    let mut failed = false;
+    std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        let result = documents.next()?;
+        let end = documents.byte_offset();
+        let frame = match result {
+            Ok(_) => Ok(message[start..end].trim_ascii()),
+            Err(error) => {
+                failed = true;
+                Err(MalformedFrame {
+                    bytes: message[start..].trim_ascii(),
+                    error,
+                })
+            }
+        };
+        start = end;
+        Some(frame)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the frames as strings, with malformed frames prefixed by `!`
+    fn frames(message: &str) -> Vec<String> {
+        split_frames(message.as_bytes())
+            .map(|frame| match frame {
+                Ok(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+                Err(malformed) => format!("!{}", String::from_utf8_lossy(malformed.bytes)),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn single_document() {
+        assert_eq!(frames(r#"{"a":1}"#), [r#"{"a":1}"#]);
+    }
+
+    #[test]
+    fn concatenated_documents() {
+        assert_eq!(
+            frames(r#"{"a":1}{"b":2} {"c":3}"#),
+            [r#"{"a":1}"#, r#"{"b":2}"#, r#"{"c":3}"#]
+        );
+    }
+
+    #[test]
+    fn brace_pair_inside_a_string() {
+        assert_eq!(
+            frames(r#"{"a":"}{"}{"b":"{}"}"#),
+            [r#"{"a":"}{"}"#, r#"{"b":"{}"}"#]
+        );
+    }
+
+    #[test]
+    fn nested_objects() {
+        assert_eq!(
+            frames(r#"{"a":{"b":{"c":1}}}{"d":[{"e":2}]}"#),
+            [r#"{"a":{"b":{"c":1}}}"#, r#"{"d":[{"e":2}]}"#]
+        );
+    }
+
+    #[test]
+    fn malformed_tail_is_returned_once() {
+        assert_eq!(
+            frames(r#"{"a":"}{"}{"b":{"c":1}} garbage {"d":1}"#),
+            [r#"{"a":"}{"}"#, r#"{"b":{"c":1}}"#, r#"!garbage {"d":1}"#]
+        );
+    }
+
+    #[test]
+    fn truncated_document() {
+        assert_eq!(frames(r#"{"a":1}{"b":"#), [r#"{"a":1}"#, r#"!{"b":"#]);
+    }
+
+    #[test]
+    fn empty_message() {
+        assert!(frames("  ").is_empty());
+    }
+}
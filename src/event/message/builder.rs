@@ -0,0 +1,131 @@
+use serde_json::Value;
+
+use crate::data::{Character, World};
+
+use super::events::EventType;
+use super::{EventRequest, EventRequestAction};
+
+/// the string the server uses to mean every character
+const ALL_CHARACTERS: &str = "all";
+
+impl EventRequest {
+    fn with_action(action: EventRequestAction) -> Self {
+        EventRequest {
+            action,
+            ..Default::default()
+        }
+    }
+
+    /// start building a request to subscribe to more characters, worlds and events
+    pub fn subscribe() -> SubscriptionRequestBuilder {
+        SubscriptionRequestBuilder::new(EventRequestAction::Subscribe)
+    }
+    /// start building a request to unsubscribe from some characters, worlds and events
+    pub fn clear_subscribe() -> SubscriptionRequestBuilder {
+        SubscriptionRequestBuilder::new(EventRequestAction::ClearSubscribe)
+    }
+    /// a request to unsubscribe from everything
+    pub fn clear_all() -> Self {
+        EventRequest {
+            all: Some(true),
+            ..Self::with_action(EventRequestAction::ClearSubscribe)
+        }
+    }
+    /// a request for the server to send back the payload
+    pub fn echo(payload: Value) -> Self {
+        EventRequest {
+            payload: Some(payload),
+            ..Self::with_action(EventRequestAction::Echo)
+        }
+    }
+    /// a request for the server to send its help message
+    pub fn help() -> Self {
+        Self::with_action(EventRequestAction::Help)
+    }
+    /// a request for the ids of all the characters that have recently been online
+    pub fn recent_character_ids() -> Self {
+        Self::with_action(EventRequestAction::RecentCharacterIds)
+    }
+    /// a request for the number of characters that have recently been online
+    pub fn recent_character_ids_count() -> Self {
+        Self::with_action(EventRequestAction::RecentCharacterIdsCount)
+    }
+}
+
+/// builds a subscribe or clearSubscribe request, only allowing the fields that make sense for them
+#[derive(Debug, Clone)]
+pub struct SubscriptionRequestBuilder {
+    request: EventRequest,
+}
+impl SubscriptionRequestBuilder {
+    fn new(action: EventRequestAction) -> Self {
+        SubscriptionRequestBuilder {
+            request: EventRequest::with_action(action),
+        }
+    }
+
+    pub fn character(self, character: Character) -> Self {
+        self.characters([character])
+    }
+    pub fn characters(mut self, characters: impl IntoIterator<Item = Character>) -> Self {
+        extend_field(
+            &mut self.request.characters,
+            characters.into_iter().map(|c| c.to_string()),
+        );
+        self
+    }
+    /// every character, instead of a list of them
+    pub fn all_characters(mut self) -> Self {
+        extend_field(&mut self.request.characters, [ALL_CHARACTERS.to_owned()]);
+        self
+    }
+
+    pub fn world(self, world: World) -> Self {
+        self.worlds([world])
+    }
+    pub fn worlds(mut self, worlds: impl IntoIterator<Item = World>) -> Self {
+        extend_field(&mut self.request.worlds, worlds);
+        self
+    }
+
+    pub fn event(self, event: EventType) -> Self {
+        self.events([event])
+    }
+    pub fn events(mut self, events: impl IntoIterator<Item = EventType>) -> Self {
+        extend_field(&mut self.request.event_names, events);
+        self
+    }
+
+    /// how subscribed characters and worlds are combined for the whole connection
+    ///
+    /// by default (`false`) the server sends every event for the subscribed characters on any world, plus every event on the subscribed worlds, so subscribing to a squad and a world gets everything on that world
+    ///
+    /// with `true` only events that match both a subscribed character and a subscribed world are sent, so a squad can be followed only while they are on a given server
+    pub fn logical_and_characters_with_worlds(mut self, logical_and: bool) -> Self {
+        self.request.logical_and_characters_with_worlds = Some(logical_and);
+        self
+    }
+
+    /// have the server list every subscribed character in its response, instead of just the count
+    pub fn list_characters(mut self, list: bool) -> Self {
+        self.request.list_characters = Some(list);
+        self
+    }
+
+    pub fn build(self) -> EventRequest {
+        self.request
+    }
+}
+impl From<SubscriptionRequestBuilder> for EventRequest {
+    fn from(builder: SubscriptionRequestBuilder) -> Self {
+        builder.build()
+    }
+}
+
+/// add to one of the lists in a request, leaving it out of the request if there is nothing to add
+fn extend_field<T>(field: &mut Option<Vec<T>>, items: impl IntoIterator<Item = T>) {
+    let mut items = items.into_iter().peekable();
+    if items.peek().is_some() {
+        field.get_or_insert_with(Vec::new).extend(items);
+    }
+}
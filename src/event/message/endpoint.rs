@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_aux::field_attributes::deserialize_bool_from_anything;
+
+use crate::data::{Environment, World};
+
+const ENDPOINT_PREFIX: &str = "EventServerEndpoint_";
+
+/// works out which world an event server endpoint name like `EventServerEndpoint_Connery_1` is for
+///
+/// returns `None` if the name isn't an endpoint, worlds the crate doesn't know about come back as [`World::Other`]
+pub fn parse_endpoint_name(name: &str) -> Option<(Environment, World)> {
+    // the world name is only there for people reading it, the id at the end is what matters
+    let (_, id) = name.strip_prefix(ENDPOINT_PREFIX)?.rsplit_once('_')?;
+    let world = World::from(u32::from_str(id).ok()?);
+    Some((world.environment(), world))
+}
+
+/// which worlds' event server endpoints are online, as sent in every heartbeat
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EndpointStatus(HashMap<(Environment, World), bool>);
+impl EndpointStatus {
+    /// if the endpoint for the world is online, `None` if the heartbeat didn't mention it
+    pub fn is_online(&self, environment: Environment, world: World) -> Option<bool> {
+        self.0.get(&(environment, world)).copied()
+    }
+
+    /// every endpoint in the heartbeat, and if it is online
+    pub fn iter(&self) -> impl Iterator<Item = (Environment, World, bool)> + '_ {
+        self.0
+            .iter()
+            .map(|((environment, world), online)| (*environment, *world, *online))
+    }
+
+    /// the endpoints that are different in `self` compared to an older status, along with their new state
+    pub fn changes_since<'a>(
+        &'a self,
+        old: &'a EndpointStatus,
+    ) -> impl Iterator<Item = (Environment, World, bool)> + 'a {
+        self.iter().filter(move |(environment, world, online)| {
+            old.is_online(*environment, *world) != Some(*online)
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for EndpointStatus {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Online(#[serde(deserialize_with = "deserialize_bool_from_anything")] bool);
+
+        struct EndpointStatusVisitor;
+        impl<'de> Visitor<'de> for EndpointStatusVisitor {
+            type Value = EndpointStatus;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a map of event server endpoints to their online state")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut status = HashMap::new();
+                while let Some((name, online)) = map.next_entry::<String, Online>()? {
+                    // keys that aren't endpoint names are skipped, unknown worlds come back as `World::Other`
+                    if let Some(key) = parse_endpoint_name(&name) {
+                        status.insert(key, online.0);
+                    }
+                }
+                Ok(EndpointStatus(status))
+            }
+        }
+
+        deserializer.deserialize_map(EndpointStatusVisitor)
+    }
+}
+
+/// a world's event server endpoint going online or offline, decoded from a serviceStateChanged message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceStateChange {
+    /// the environment and world of the endpoint, `None` if the crate doesn't recognise the endpoint
+    pub endpoint: Option<(Environment, World)>,
+    pub online: bool,
+    /// the raw endpoint name, like `EventServerEndpoint_Emerald_17`
+    pub detail: String,
+}
+impl ServiceStateChange {
+    pub fn new(detail: &str, online: bool) -> Self {
+        ServiceStateChange {
+            endpoint: parse_endpoint_name(detail),
+            online,
+            detail: detail.to_owned(),
+        }
+    }
+
+    pub fn environment(&self) -> Option<Environment> {
+        self.endpoint.map(|(environment, _)| environment)
+    }
+    pub fn world(&self) -> Option<World> {
+        self.endpoint.map(|(_, world)| world)
+    }
+}
+impl fmt::Display for ServiceStateChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = if self.online { "online" } else { "offline" };
+        match self.world() {
+            Some(world) => write!(f, "{:?} event feed went {}", world, state),
+            None => write!(f, "{} went {}", self.detail, state),
+        }
+    }
+}
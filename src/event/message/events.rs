@@ -1,16 +1,19 @@
-use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use serde::de::{Error as _, Unexpected};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_aux::field_attributes::{deserialize_bool_from_anything, deserialize_number_from_string};
+use serde_json::Value;
 
 use crate::data::{
     Achievement, BattleRank, Character, Experience, Facility, Faction, FireMode, Item, Loadout,
-    Outfit, Skill, Timestamp, Vehicle, Weapon, World, Zone,
+    MetagameEventId, MetagameEventState, Outfit, Skill, Timestamp, Vehicle, Weapon, World, Zone,
+    ZoneInstance,
 };
 
-// LOTS of issues with strange zone_id numbers
-
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+/// an event name to subscribe to
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
 pub enum EventType {
-    #[serde(rename = "all")]
     All,
 
     AchievementEarned,
@@ -20,6 +23,8 @@ pub enum EventType {
     SkillAdded,
     VehicleDestroy,
     GainExperience,
+    /// only `GainExperience` events for one experience id, sent as `GainExperience_experience_id_<id>`
+    GainExperienceId(Experience),
 
     PlayerFacilityCapture,
     PlayerFacilityDefend,
@@ -34,8 +39,83 @@ pub enum EventType {
     PlayerLogout,
 }
 
-#[derive(Deserialize, Debug, Clone)]
-#[serde(tag = "event_name")]
+const EXPERIENCE_ID_PREFIX: &str = "GainExperience_experience_id_";
+
+impl EventType {
+    /// every event type without a parameter, in the same order as [`EventType`]
+    const NAMED: &'static [(&'static str, EventType)] = &[
+        ("all", EventType::All),
+        ("AchievementEarned", EventType::AchievementEarned),
+        ("BattleRankUp", EventType::BattleRankUp),
+        ("Death", EventType::Death),
+        ("ItemAdded", EventType::ItemAdded),
+        ("SkillAdded", EventType::SkillAdded),
+        ("VehicleDestroy", EventType::VehicleDestroy),
+        ("GainExperience", EventType::GainExperience),
+        ("PlayerFacilityCapture", EventType::PlayerFacilityCapture),
+        ("PlayerFacilityDefend", EventType::PlayerFacilityDefend),
+        ("ContinentLock", EventType::ContinentLock),
+        ("ContinentUnlock", EventType::ContinentUnlock),
+        ("FacilityControl", EventType::FacilityControl),
+        ("MetagameEvent", EventType::MetagameEvent),
+        ("PlayerLogin", EventType::PlayerLogin),
+        ("PlayerLogout", EventType::PlayerLogout),
+    ];
+
+    /// the event type for an event name as the server writes it
+    fn from_name(name: &str) -> Option<EventType> {
+        if let Some(id) = name.strip_prefix(EXPERIENCE_ID_PREFIX) {
+            return id.parse().ok().map(EventType::GainExperienceId);
+        }
+        Self::NAMED
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, event)| *event)
+    }
+}
+impl fmt::Display for EventType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventType::GainExperienceId(experience) => {
+                write!(f, "{}{}", EXPERIENCE_ID_PREFIX, experience.0)
+            }
+            event => {
+                let (name, _) = Self::NAMED
+                    .iter()
+                    .find(|(_, e)| e == event)
+                    .expect("every event type without a parameter has a name");
+                f.write_str(name)
+            }
+        }
+    }
+}
+impl Serialize for EventType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+impl<'de> Deserialize<'de> for EventType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let name = String::deserialize(deserializer)?;
+        EventType::from_name(&name).ok_or_else(|| {
+            D::Error::invalid_value(
+                Unexpected::Str(&name),
+                &"an event name, or GainExperience_experience_id_<id>",
+            )
+        })
+    }
+}
+
+/// an event sent by the server, tagged by its `event_name`
+///
+/// events with a name the crate doesn't know, or that are missing fields or have fields of the wrong type, deserialize as `Unknown` instead of failing, use [`EventPayload::parse_strict`] to find out why
+#[derive(Debug, Clone)]
 pub enum EventPayload {
     AchievementEarned(AchievementEvent),
     BattleRankUp(BattleRankEvent),
@@ -56,30 +136,284 @@ pub enum EventPayload {
 
     PlayerLogin(PlayerLogEvent),
     PlayerLogout(PlayerLogEvent),
+
+    /// an event that couldn't be parsed as any of the other variants, along with the whole payload
+    Unknown {
+        event_name: String,
+        raw: Value,
+    },
 }
 
-// TODO: some of these may be numbers
-#[derive(Deserialize, Debug, Clone)]
+/// the event names the crate can parse, in the same order as [`EventPayload`], the tests check this matches `EventType::NAMED`, `parse_strict` and `event_name`
+const EVENT_NAMES: &[&str] = &[
+    "AchievementEarned",
+    "BattleRankUp",
+    "Death",
+    "ItemAdded",
+    "SkillAdded",
+    "VehicleDestroy",
+    "GainExperience",
+    "PlayerFacilityCapture",
+    "PlayerFacilityDefend",
+    "ContinentLock",
+    "ContinentUnlock",
+    "FacilityControl",
+    "MetagameEvent",
+    "PlayerLogin",
+    "PlayerLogout",
+];
+
+impl<'de> Deserialize<'de> for EventPayload {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = Value::deserialize(deserializer)?;
+        let event_name = match raw.get("event_name") {
+            Some(Value::String(name)) => name.clone(),
+            Some(_) => return Err(D::Error::custom("`event_name` is not a string")),
+            None => return Err(D::Error::missing_field("event_name")),
+        };
+        Ok(Self::parse_strict(&raw).unwrap_or(EventPayload::Unknown { event_name, raw }))
+    }
+}
+
+impl EventPayload {
+    /// parse a payload without falling back to `Unknown`, so the error says exactly what didn't match
+    pub fn parse_strict(raw: &Value) -> Result<EventPayload, serde_json::Error> {
+        use serde_json::Error;
+
+        let event_name = match raw.get("event_name") {
+            Some(Value::String(name)) => name.as_str(),
+            Some(_) => return Err(Error::custom("`event_name` is not a string")),
+            None => return Err(Error::missing_field("event_name")),
+        };
+        match event_name {
+            "AchievementEarned" => {
+                AchievementEvent::deserialize(raw).map(EventPayload::AchievementEarned)
+            }
+            "BattleRankUp" => BattleRankEvent::deserialize(raw).map(EventPayload::BattleRankUp),
+            "Death" => DeathEvent::deserialize(raw).map(EventPayload::Death),
+            "ItemAdded" => ItemAddEvent::deserialize(raw).map(EventPayload::ItemAdded),
+            "SkillAdded" => SkillAddEvent::deserialize(raw).map(EventPayload::SkillAdded),
+            "VehicleDestroy" => {
+                VehicleDestroyEvent::deserialize(raw).map(EventPayload::VehicleDestroy)
+            }
+            "GainExperience" => ExperienceEvent::deserialize(raw).map(EventPayload::GainExperience),
+            "PlayerFacilityCapture" => {
+                PlayerFacilityEvent::deserialize(raw).map(EventPayload::PlayerFacilityCapture)
+            }
+            "PlayerFacilityDefend" => {
+                PlayerFacilityEvent::deserialize(raw).map(EventPayload::PlayerFacilityDefend)
+            }
+            "ContinentLock" => ContinentEvent::deserialize(raw).map(EventPayload::ContinentLock),
+            "ContinentUnlock" => {
+                ContinentEvent::deserialize(raw).map(EventPayload::ContinentUnlock)
+            }
+            "FacilityControl" => {
+                FacilityControlEvent::deserialize(raw).map(EventPayload::FacilityControl)
+            }
+            "MetagameEvent" => MetagameEvent::deserialize(raw).map(EventPayload::MetagameEvent),
+            "PlayerLogin" => PlayerLogEvent::deserialize(raw).map(EventPayload::PlayerLogin),
+            "PlayerLogout" => PlayerLogEvent::deserialize(raw).map(EventPayload::PlayerLogout),
+            name => Err(Error::unknown_variant(name, EVENT_NAMES)),
+        }
+    }
+
+    /// the `event_name` the server sent
+    pub fn event_name(&self) -> &str {
+        match self {
+            EventPayload::AchievementEarned(_) => "AchievementEarned",
+            EventPayload::BattleRankUp(_) => "BattleRankUp",
+            EventPayload::Death(_) => "Death",
+            EventPayload::ItemAdded(_) => "ItemAdded",
+            EventPayload::SkillAdded(_) => "SkillAdded",
+            EventPayload::VehicleDestroy(_) => "VehicleDestroy",
+            EventPayload::GainExperience(_) => "GainExperience",
+            EventPayload::PlayerFacilityCapture(_) => "PlayerFacilityCapture",
+            EventPayload::PlayerFacilityDefend(_) => "PlayerFacilityDefend",
+            EventPayload::ContinentLock(_) => "ContinentLock",
+            EventPayload::ContinentUnlock(_) => "ContinentUnlock",
+            EventPayload::FacilityControl(_) => "FacilityControl",
+            EventPayload::MetagameEvent(_) => "MetagameEvent",
+            EventPayload::PlayerLogin(_) => "PlayerLogin",
+            EventPayload::PlayerLogout(_) => "PlayerLogout",
+            EventPayload::Unknown { event_name, .. } => event_name,
+        }
+    }
+
+    /// the world the event happened on, only `None` for unknown events without a recognisable `world_id`
+    pub fn world(&self) -> Option<World> {
+        Some(match self {
+            EventPayload::AchievementEarned(e) => e.world_id,
+            EventPayload::BattleRankUp(e) => e.world_id,
+            EventPayload::Death(e) => e.world_id,
+            EventPayload::ItemAdded(e) => e.world_id,
+            EventPayload::SkillAdded(e) => e.world_id,
+            EventPayload::VehicleDestroy(e) => e.world_id,
+            EventPayload::GainExperience(e) => e.world_id,
+            EventPayload::PlayerFacilityCapture(e) | EventPayload::PlayerFacilityDefend(e) => {
+                e.world_id
+            }
+            EventPayload::ContinentLock(e) | EventPayload::ContinentUnlock(e) => e.world_id,
+            EventPayload::FacilityControl(e) => e.world_id,
+            EventPayload::MetagameEvent(e) => e.world_id,
+            EventPayload::PlayerLogin(e) | EventPayload::PlayerLogout(e) => e.world_id,
+            EventPayload::Unknown { raw, .. } => {
+                return raw_id(raw, "world_id").map(|id| World::from(id as u32))
+            }
+        })
+    }
+
+    /// the zone and instance the event happened in, `None` for logins and logouts, and unknown events without a recognisable `zone_id`
+    pub fn zone_instance(&self) -> Option<ZoneInstance> {
+        match self {
+            EventPayload::AchievementEarned(e) => Some(e.zone_id),
+            EventPayload::BattleRankUp(e) => Some(e.zone_id),
+            EventPayload::Death(e) => Some(e.zone_id),
+            EventPayload::ItemAdded(e) => Some(e.zone_id),
+            EventPayload::SkillAdded(e) => Some(e.zone_id),
+            EventPayload::VehicleDestroy(e) => Some(e.zone_id),
+            EventPayload::GainExperience(e) => Some(e.zone_id),
+            EventPayload::PlayerFacilityCapture(e) | EventPayload::PlayerFacilityDefend(e) => {
+                Some(e.zone_id)
+            }
+            EventPayload::ContinentLock(e) | EventPayload::ContinentUnlock(e) => Some(e.zone_id),
+            EventPayload::FacilityControl(e) => Some(e.zone_id),
+            EventPayload::MetagameEvent(e) => Some(e.zone_id),
+            EventPayload::PlayerLogin(_) | EventPayload::PlayerLogout(_) => None,
+            EventPayload::Unknown { raw, .. } => {
+                raw_id(raw, "zone_id").map(|id| ZoneInstance::from(id as u32))
+            }
+        }
+    }
+
+    /// the zone definition the event happened in, without the instance, see [`EventPayload::zone_instance`]
+    ///
+    /// `None` as well for zones the crate doesn't know about
+    pub fn zone(&self) -> Option<Zone> {
+        self.zone_instance()?.zone()
+    }
+
+    /// every character involved in the event, so both the attacker and the victim of a death
+    ///
+    /// for experience events this includes `other_id`, which is only sometimes a character
+    pub fn characters(&self) -> impl Iterator<Item = Character> {
+        let characters = match self {
+            EventPayload::AchievementEarned(e) => [Some(e.character_id), None],
+            EventPayload::BattleRankUp(e) => [Some(e.character_id), None],
+            EventPayload::Death(e) => [Some(e.character_id), Some(e.attacker_character_id)],
+            EventPayload::ItemAdded(e) => [Some(e.character_id), None],
+            EventPayload::SkillAdded(e) => [Some(e.character_id), None],
+            EventPayload::VehicleDestroy(e) => {
+                [Some(e.character_id), Some(e.attacker_character_id)]
+            }
+            EventPayload::GainExperience(e) => [Some(e.character_id), Some(e.other_id)],
+            EventPayload::PlayerFacilityCapture(e) | EventPayload::PlayerFacilityDefend(e) => {
+                [Some(e.character_id), None]
+            }
+            EventPayload::PlayerLogin(e) | EventPayload::PlayerLogout(e) => {
+                [Some(e.character_id), None]
+            }
+            EventPayload::ContinentLock(_)
+            | EventPayload::ContinentUnlock(_)
+            | EventPayload::FacilityControl(_)
+            | EventPayload::MetagameEvent(_) => [None, None],
+            EventPayload::Unknown { raw, .. } => [
+                raw_id(raw, "character_id").map(Character),
+                raw_id(raw, "attacker_character_id").map(Character),
+            ],
+        };
+        characters.into_iter().flatten()
+    }
+
+    /// every faction named in the event, events that only name characters have none
+    pub fn factions(&self) -> impl Iterator<Item = Faction> {
+        let factions = match self {
+            EventPayload::VehicleDestroy(e) => [Some(e.faction_id), None],
+            EventPayload::ContinentLock(e) | EventPayload::ContinentUnlock(e) => {
+                [Some(e.previous_faction), Some(e.triggering_faction)]
+            }
+            EventPayload::FacilityControl(e) => [Some(e.old_faction_id), Some(e.new_faction_id)],
+            _ => [None, None],
+        };
+        factions.into_iter().flatten()
+    }
+
+    /// the experience that was gained, `None` for anything but experience events
+    pub fn experience(&self) -> Option<Experience> {
+        match self {
+            EventPayload::GainExperience(e) => Some(e.experience_id),
+            _ => None,
+        }
+    }
+}
+
+/// an id field from a raw payload, which the server usually sends as a string
+fn raw_id(raw: &Value, field: &str) -> Option<u64> {
+    match raw.get(field)? {
+        Value::String(id) => id.parse().ok(),
+        id => id.as_u64(),
+    }
+}
+
+/// one of the payload structs inside an [`EventPayload`], so listeners can ask for a specific kind of event
+pub trait EventKind: Sized {
+    /// the event inside the payload, if it is this kind of event
+    fn from_payload(payload: &EventPayload) -> Option<&Self>;
+}
+
+macro_rules! impl_event_kind {
+    ($kind:ty => $($variant:ident),+) => {
+        impl EventKind for $kind {
+            fn from_payload(payload: &EventPayload) -> Option<&Self> {
+                match payload {
+                    $(EventPayload::$variant(e))|+ => Some(e),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+impl_event_kind!(AchievementEvent => AchievementEarned);
+impl_event_kind!(BattleRankEvent => BattleRankUp);
+impl_event_kind!(DeathEvent => Death);
+impl_event_kind!(ItemAddEvent => ItemAdded);
+impl_event_kind!(SkillAddEvent => SkillAdded);
+impl_event_kind!(VehicleDestroyEvent => VehicleDestroy);
+impl_event_kind!(ExperienceEvent => GainExperience);
+impl_event_kind!(PlayerFacilityEvent => PlayerFacilityCapture, PlayerFacilityDefend);
+impl_event_kind!(ContinentEvent => ContinentLock, ContinentUnlock);
+impl_event_kind!(FacilityControlEvent => FacilityControl);
+impl_event_kind!(MetagameEvent => MetagameEvent);
+impl_event_kind!(PlayerLogEvent => PlayerLogin, PlayerLogout);
+
+#[derive(Deserialize, Debug, Clone, Copy)]
 pub struct ContinentEvent {
     // continent lock/unlock
-    pub event_type: String,
-    pub metagame_event_id: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub event_type: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub metagame_event_id: MetagameEventId,
 
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub previous_faction: Faction,
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub triggering_faction: Faction,
 
-    pub vs_population: String,
-    pub nc_population: String,
-    pub tr_population: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub vs_population: f32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub nc_population: f32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub tr_population: f32,
 
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub timestamp: Timestamp,
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub world_id: World,
     #[serde(deserialize_with = "deserialize_number_from_string")]
-    pub zone_id: Zone,
+    pub zone_id: ZoneInstance,
 }
 
 #[derive(Deserialize, Debug, Clone, Copy)]
@@ -94,7 +428,7 @@ pub struct AchievementEvent {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub world_id: World,
     #[serde(deserialize_with = "deserialize_number_from_string")]
-    pub zone_id: Zone,
+    pub zone_id: ZoneInstance,
 }
 
 #[derive(Deserialize, Debug, Clone, Copy)]
@@ -109,7 +443,7 @@ pub struct BattleRankEvent {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub world_id: World,
     #[serde(deserialize_with = "deserialize_number_from_string")]
-    pub zone_id: Zone,
+    pub zone_id: ZoneInstance,
 }
 
 /// Player login/logout
@@ -142,27 +476,34 @@ pub struct FacilityControlEvent {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub world_id: World,
     #[serde(deserialize_with = "deserialize_number_from_string")]
-    pub zone_id: Zone,
+    pub zone_id: ZoneInstance,
 }
 
-// TODO: some of these may be numbers
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, Copy)]
 pub struct MetagameEvent {
-    pub metagame_event_id: String,
-    pub metagame_event_state: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub metagame_event_id: MetagameEventId,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub metagame_event_state: MetagameEventState,
 
-    pub experience_bonus: String,
+    /// the experience bonus in percent, like `25.0`
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub experience_bonus: f32,
 
-    pub faction_nc: String,
-    pub faction_tr: String,
-    pub faction_vs: String,
+    /// the share of the territory each faction holds, in percent
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub faction_nc: f32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub faction_tr: f32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub faction_vs: f32,
 
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub timestamp: Timestamp,
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub world_id: World,
     #[serde(deserialize_with = "deserialize_number_from_string")]
-    pub zone_id: Zone,
+    pub zone_id: ZoneInstance,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -181,7 +522,7 @@ pub struct ItemAddEvent {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub world_id: World,
     #[serde(deserialize_with = "deserialize_number_from_string")]
-    pub zone_id: Zone,
+    pub zone_id: ZoneInstance,
 }
 
 #[derive(Deserialize, Debug, Clone, Copy)]
@@ -196,7 +537,7 @@ pub struct SkillAddEvent {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub world_id: World,
     #[serde(deserialize_with = "deserialize_number_from_string")]
-    pub zone_id: Zone,
+    pub zone_id: ZoneInstance,
 }
 
 /// Player facility capture/defend
@@ -214,7 +555,7 @@ pub struct PlayerFacilityEvent {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub world_id: World,
     #[serde(deserialize_with = "deserialize_number_from_string")]
-    pub zone_id: Zone,
+    pub zone_id: ZoneInstance,
 }
 
 #[derive(Deserialize, Debug, Clone, Copy)]
@@ -236,7 +577,7 @@ pub struct ExperienceEvent {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub world_id: World,
     #[serde(deserialize_with = "deserialize_number_from_string")]
-    pub zone_id: Zone,
+    pub zone_id: ZoneInstance,
 }
 
 #[derive(Deserialize, Debug, Clone, Copy)]
@@ -265,7 +606,7 @@ pub struct DeathEvent {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub world_id: World,
     #[serde(deserialize_with = "deserialize_number_from_string")]
-    pub zone_id: Zone,
+    pub zone_id: ZoneInstance,
 }
 
 #[derive(Deserialize, Debug, Clone, Copy)]
@@ -293,5 +634,119 @@ pub struct VehicleDestroyEvent {
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub world_id: World,
     #[serde(deserialize_with = "deserialize_number_from_string")]
-    pub zone_id: Zone,
+    pub zone_id: ZoneInstance,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a payload with every field any event has, so it parses as whichever event it is named as
+    fn payload(event_name: &str) -> Value {
+        let mut raw = serde_json::json!({
+            "event_name": event_name,
+            "metagame_event_state": "135",
+            "context": "SkillAdded",
+        });
+        for field in [
+            "achievement_id",
+            "amount",
+            "attacker_character_id",
+            "attacker_fire_mode_id",
+            "attacker_loadout_id",
+            "attacker_vehicle_id",
+            "attacker_weapon_id",
+            "battle_rank",
+            "character_id",
+            "character_loadout_id",
+            "event_type",
+            "experience_bonus",
+            "experience_id",
+            "facility_id",
+            "faction_id",
+            "faction_nc",
+            "faction_tr",
+            "faction_vs",
+            "is_headshot",
+            "item_count",
+            "item_id",
+            "loadout_id",
+            "metagame_event_id",
+            "nc_population",
+            "new_faction_id",
+            "old_faction_id",
+            "other_id",
+            "outfit_id",
+            "previous_faction",
+            "skill_id",
+            "timestamp",
+            "tr_population",
+            "triggering_faction",
+            "vehicle_id",
+            "vs_population",
+            "world_id",
+            "zone_id",
+        ] {
+            raw[field] = "1".into();
+        }
+        raw
+    }
+
+    #[test]
+    fn event_name_lists_agree() {
+        let named: Vec<&str> = EventType::NAMED
+            .iter()
+            .map(|(name, _)| *name)
+            .filter(|name| *name != "all")
+            .collect();
+        assert_eq!(named, EVENT_NAMES);
+
+        for name in EVENT_NAMES {
+            let event = EventPayload::parse_strict(&payload(name))
+                .unwrap_or_else(|e| panic!("{} didn't parse: {}", name, e));
+            assert_eq!(event.event_name(), *name);
+        }
+    }
+
+    #[test]
+    fn unknown_event_names_fall_back() {
+        let raw = payload("SomethingNew");
+        let error = EventPayload::parse_strict(&raw).unwrap_err();
+        assert!(error.to_string().contains("unknown variant"), "{}", error);
+        let event: EventPayload = serde_json::from_value(raw).unwrap();
+        assert!(matches!(event, EventPayload::Unknown { .. }));
+        assert_eq!(event.event_name(), "SomethingNew");
+    }
+
+    #[test]
+    fn event_types_round_trip() {
+        let mut types: Vec<EventType> = EventType::NAMED.iter().map(|(_, e)| *e).collect();
+        types.push(EventType::GainExperienceId(Experience(7)));
+        for event in types {
+            let json = serde_json::to_string(&event).unwrap();
+            assert_eq!(serde_json::from_str::<EventType>(&json).unwrap(), event);
+        }
+
+        assert_eq!(serde_json::to_string(&EventType::All).unwrap(), r#""all""#);
+        assert_eq!(
+            serde_json::to_string(&EventType::GainExperienceId(Experience(7))).unwrap(),
+            r#""GainExperience_experience_id_7""#
+        );
+        assert_eq!(
+            serde_json::from_str::<EventType>(r#""GainExperience_experience_id_53""#).unwrap(),
+            EventType::GainExperienceId(Experience(53))
+        );
+    }
+
+    #[test]
+    fn unknown_event_types_are_rejected() {
+        for name in [
+            r#""SomethingNew""#,
+            r#""All""#,
+            r#""GainExperience_experience_id_""#,
+            r#""GainExperience_experience_id_abc""#,
+        ] {
+            assert!(serde_json::from_str::<EventType>(name).is_err(), "{}", name);
+        }
+    }
 }
@@ -1,19 +1,24 @@
-use serde::{Deserialize, Serialize};
-use serde_aux::field_attributes::deserialize_bool_from_anything;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_aux::field_attributes::{deserialize_bool_from_anything, deserialize_number_from_string};
 use serde_json::Value;
 
-use crate::data::World;
+use crate::data::{Character, World};
 
 pub mod events;
 use events::{EventPayload, EventType};
 
-#[derive(Serialize, Deserialize, Debug)]
+mod builder;
+pub use builder::SubscriptionRequestBuilder;
+mod endpoint;
+pub use endpoint::{parse_endpoint_name, EndpointStatus, ServiceStateChange};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
 pub enum Service {
     Event,
     Push,
 }
-#[derive(Serialize)]
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub enum EventRequestAction {
     Echo,
@@ -24,7 +29,7 @@ pub enum EventRequestAction {
     RecentCharacterIdsCount,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Debug, Clone)]
 pub struct EventRequest {
     /// the service you are using, always "event"
     pub service: Service,
@@ -34,7 +39,7 @@ pub struct EventRequest {
     /// used for the clearSubscribe request to specify to clear all subscriptions
     #[serde(skip_serializing_if = "Option::is_none")]
     pub all: Option<bool>,
-    ///
+    /// used for the subscribe request to list every subscribed character in the response, instead of just the count
     #[serde(skip_serializing_if = "Option::is_none")]
     pub list_characters: Option<bool>,
 
@@ -53,6 +58,13 @@ pub struct EventRequest {
     /// the names of the events you are subscribing to
     #[serde(skip_serializing_if = "Option::is_none", rename = "eventNames")]
     pub event_names: Option<Vec<EventType>>,
+
+    /// when true, events for the subscribed characters are only sent if they happen on one of the subscribed worlds, instead of getting every event for the characters as well as every event on the worlds
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        rename = "logicalAndCharactersWithWorlds"
+    )]
+    pub logical_and_characters_with_worlds: Option<bool>,
 }
 impl Default for EventRequest {
     fn default() -> Self {
@@ -67,39 +79,45 @@ impl Default for EventRequest {
             characters: Option::None,
             worlds: Option::None,
             event_names: Option::None,
+
+            logical_and_characters_with_worlds: Option::None,
         }
     }
 }
 
-#[allow(dead_code)]
+/// the server's confirmation of everything the connection is subscribed to, sent after every subscribe and clearSubscribe request
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct SubscriptionInfoEventResponse {
-    character_count: Option<i32>,
-    characters: Option<Vec<String>>,
-    event_names: Vec<EventType>,
-    logical_and_characters_with_worlds: bool,
-    worlds: Vec<String>, // TODO: fix world deserializing
+    pub character_count: Option<i32>,
+    /// only sent when `list_characters` was set on the request
+    pub characters: Option<Vec<String>>,
+    pub event_names: Vec<EventType>,
+    pub logical_and_characters_with_worlds: bool,
+    pub worlds: Vec<World>,
 }
 
 #[derive(Deserialize, Debug)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum InternallyTaggedEventResponse {
+    /// sent regularly, with the state of each world's event server endpoint
     Heartbeat {
         service: Service,
-        online: Value,
+        online: EndpointStatus,
     },
     /// a message with data about an event you have subscribed to
     ServiceMessage {
         service: Service,
         payload: EventPayload,
     },
+    /// a world's event server endpoint has gone online or offline, `detail` is the endpoint name
     ServiceStateChanged {
         service: Service,
         #[serde(deserialize_with = "deserialize_bool_from_anything")]
         online: bool,
         detail: String,
     },
+    /// sent when the connection to the push server is established
     ConnectionStateChanged {
         service: Service,
         #[serde(deserialize_with = "deserialize_bool_from_anything")]
@@ -110,6 +128,36 @@ pub enum InternallyTaggedEventResponse {
 #[serde(rename_all = "camelCase")]
 pub enum ExternallyTaggedEventResponse {
     Subscription(SubscriptionInfoEventResponse),
+    /// the reply to a recentCharacterIds request
+    #[serde(rename = "recent_character_id_list")]
+    RecentCharacterIds(RecentCharacterIdsEventResponse),
+    /// the reply to a recentCharacterIdsCount request
+    #[serde(rename = "recent_character_id_count")]
+    RecentCharacterIdsCount(RecentCharacterIdsCountEventResponse),
+}
+
+/// the characters that have recently been online
+#[derive(Deserialize, Debug, Clone)]
+pub struct RecentCharacterIdsEventResponse(
+    #[serde(deserialize_with = "deserialize_characters")] pub Vec<Character>,
+);
+
+/// the number of characters that have recently been online
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct RecentCharacterIdsCountEventResponse(
+    #[serde(deserialize_with = "deserialize_number_from_string")] pub u32,
+);
+
+/// character ids are sent as strings
+fn deserialize_characters<'de, D>(deserializer: D) -> Result<Vec<Character>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct CharacterId(#[serde(deserialize_with = "deserialize_number_from_string")] Character);
+
+    let ids = Vec::<CharacterId>::deserialize(deserializer)?;
+    Ok(ids.into_iter().map(|id| id.0).collect())
 }
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase", untagged)]
@@ -133,3 +181,20 @@ pub enum EventResponse {
     ExternallyTagged(ExternallyTaggedEventResponse),
     Untagged(UntaggedEventResponse),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subscription_info_with_string_worlds() {
+        let info: SubscriptionInfoEventResponse = serde_json::from_str(
+            r#"{"characterCount":0,"eventNames":["Death"],"logicalAndCharactersWithWorlds":false,"worlds":["1","17","9999"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            info.worlds,
+            [World::Connery, World::Emerald, World::Other(9999)]
+        );
+    }
+}
@@ -1,29 +1,75 @@
 const EVENT_BASE_URL: &str =
     "wss://push.planetside2.com/streaming?environment={env}&service-id=s:{service_id}";
+/// how long to wait for the reply to a request by default
+const DEFAULT_REPLY_TIMEOUT: Duration = Duration::from_secs(30);
 
-use futures_util::{SinkExt, StreamExt};
+use futures_util::{stream::BoxStream, SinkExt, StreamExt};
 use tokio::net::TcpStream;
 use tokio_tungstenite::{
-    connect_async, tungstenite::protocol::Message, MaybeTlsStream, WebSocketStream,
+    connect_async_tls_with_config, tungstenite::protocol::Message, MaybeTlsStream, WebSocketStream,
 };
 
+use serde::Deserialize;
 use serde_json::Value;
 
-use crate::data::Environment;
+use crate::data::{Character, Environment, World};
+use crate::Error;
 
-use std::error::Error;
+use std::collections::VecDeque;
+use std::future::{poll_fn, Future};
+use std::task::Poll;
+use std::time::Duration;
+use tokio::time::Instant;
 
 pub mod message;
 use message::{
-    EventRequest, EventResponse, ExternallyTaggedEventResponse, InternallyTaggedEventResponse,
+    EndpointStatus, EventRequest, EventResponse, ExternallyTaggedEventResponse,
+    InternallyTaggedEventResponse, ServiceStateChange,
 };
 
-pub use message::events::{EventPayload, EventType};
+pub use message::events::{EventKind, EventPayload, EventType};
+pub use tokio_tungstenite::Connector;
+
+mod async_listener;
+pub use async_listener::AsyncConcurrency;
+use async_listener::AsyncEventListener;
+mod filter;
+pub use filter::EventFilter;
+mod framing;
+mod reconnect;
+pub use reconnect::ReconnectPolicy;
+mod subscription;
+pub use subscription::{Subscription, SubscriptionManager, SubscriptionMismatch};
+
+type Ps2ResponseCallback = Box<dyn FnMut(&EventResponse) + Send>;
+type AllResponseCallback = Box<dyn FnMut(&Value) + Send>;
+type EventCallback = Box<dyn FnMut(&EventPayload) + Send>;
+type ErrorCallback = Box<dyn FnMut(&[u8], &serde_json::Error) + Send>;
+type SubscriptionCallback = Box<dyn FnMut(&SubscriptionManager) + Send>;
+type EndpointStatusCallback = Box<dyn FnMut(Environment, World, bool) + Send>;
+type StaleCallback = Box<dyn FnMut(Duration) + Send>;
+type ConnectionStateCallback = Box<dyn FnMut(bool) + Send>;
+type ServiceStateCallback = Box<dyn FnMut(&ServiceStateChange) + Send>;
 
 pub struct CallbackHolder {
-    ps2_callbacks: Vec<Box<dyn FnMut(&EventResponse)>>,
-    all_callbacks: Vec<Box<dyn FnMut(&Value)>>,
-    event_callbacks: Vec<Box<dyn FnMut(&EventPayload)>>,
+    ps2_callbacks: Vec<Ps2ResponseCallback>,
+    all_callbacks: Vec<AllResponseCallback>,
+    event_callbacks: Vec<EventCallback>,
+    error_callbacks: Vec<ErrorCallback>,
+    subscription_callbacks: Vec<SubscriptionCallback>,
+    endpoint_status_callbacks: Vec<EndpointStatusCallback>,
+    stale_callbacks: Vec<StaleCallback>,
+    connection_state_callbacks: Vec<ConnectionStateCallback>,
+    service_state_callbacks: Vec<ServiceStateCallback>,
+    async_event_listeners: Vec<AsyncEventListener>,
+
+    /// the endpoint status from the last heartbeat, to compare the next one against
+    last_endpoint_status: Option<EndpointStatus>,
+}
+impl Default for CallbackHolder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl CallbackHolder {
     pub fn new() -> Self {
@@ -31,70 +77,206 @@ impl CallbackHolder {
             ps2_callbacks: vec![],
             all_callbacks: vec![],
             event_callbacks: vec![],
+            error_callbacks: vec![],
+            subscription_callbacks: vec![],
+            endpoint_status_callbacks: vec![],
+            stale_callbacks: vec![],
+            connection_state_callbacks: vec![],
+            service_state_callbacks: vec![],
+            async_event_listeners: vec![],
+
+            last_endpoint_status: None,
         }
     }
 
     /// add a listener that fires on all responses from the server that parse as valid PS2 responses
     pub fn register_ps2_response_listener<F>(&mut self, callback: F)
     where
-        F: 'static + FnMut(&EventResponse),
+        F: 'static + Send + FnMut(&EventResponse),
     {
         self.ps2_callbacks.push(Box::new(callback));
     }
     /// add a listener that fires on all responses that parse as valid event payloads
     pub fn register_event_listener<F>(&mut self, callback: F)
     where
-        F: 'static + FnMut(&EventPayload),
+        F: 'static + Send + FnMut(&EventPayload),
     {
         self.event_callbacks.push(Box::new(callback));
     }
+    /// add a listener that only fires for one kind of event, and gets the event struct directly
+    ///
+    /// kinds that cover two events, like `PlayerLogEvent` for both logins and logouts, fire for both of them
+    pub fn register_typed_event_listener<E, F>(&mut self, mut callback: F)
+    where
+        E: EventKind,
+        F: 'static + Send + FnMut(&E),
+    {
+        self.register_event_listener(move |payload| {
+            if let Some(event) = E::from_payload(payload) {
+                callback(event);
+            }
+        });
+    }
+    /// add a listener that only fires for events matching the filter
+    pub fn register_filtered_event_listener<F>(&mut self, filter: EventFilter, mut callback: F)
+    where
+        F: 'static + Send + FnMut(&EventPayload),
+    {
+        self.register_event_listener(move |payload| {
+            if filter.matches(payload) {
+                callback(payload);
+            }
+        });
+    }
+    /// add an async listener for events, the futures it returns are run by the client while it waits for messages
+    ///
+    /// `concurrency` decides how many of its futures run at once, events that can't start yet are queued until they can
+    pub fn register_async_event_listener<F, Fut>(
+        &mut self,
+        concurrency: AsyncConcurrency,
+        callback: F,
+    ) where
+        F: 'static + Send + FnMut(EventPayload) -> Fut,
+        Fut: 'static + Send + Future<Output = ()>,
+    {
+        self.async_event_listeners
+            .push(AsyncEventListener::new(concurrency, callback));
+    }
     /// add a listener that fires on all responses from the server that are valid JSON, even if they cannot be parsed as valid messages for the PS2 api
     pub fn register_all_response_listener<F>(&mut self, callback: F)
     where
-        F: 'static + FnMut(&Value),
+        F: 'static + Send + FnMut(&Value),
     {
         self.all_callbacks.push(Box::new(callback));
     }
+    /// add a listener that fires with the raw message and the parse error whenever a message from the server is not valid JSON, or cannot be parsed as a PS2 response or event payload
+    ///
+    /// echo responses are whatever was sent to be echoed, so they will usually end up here too
+    ///
+    /// events that could only be parsed as `EventPayload::Unknown` end up here as well as with the event listeners, with the error saying why
+    pub fn register_error_listener<F>(&mut self, callback: F)
+    where
+        F: 'static + Send + FnMut(&[u8], &serde_json::Error),
+    {
+        self.error_callbacks.push(Box::new(callback));
+    }
+    /// add a listener that fires whenever the server confirms the subscription, after the client has updated its view of the subscription state
+    pub fn register_subscription_listener<F>(&mut self, callback: F)
+    where
+        F: 'static + Send + FnMut(&SubscriptionManager),
+    {
+        self.subscription_callbacks.push(Box::new(callback));
+    }
+    /// add a listener that fires whenever a heartbeat shows that a world's event server endpoint has gone online or offline
+    ///
+    /// the first heartbeat only sets the starting state, use `register_ps2_response_listener` to see every heartbeat
+    pub fn register_endpoint_status_listener<F>(&mut self, callback: F)
+    where
+        F: 'static + Send + FnMut(Environment, World, bool),
+    {
+        self.endpoint_status_callbacks.push(Box::new(callback));
+    }
+    /// add a listener that fires with how long the connection has been quiet when the client's idle timeout runs out, just before the connection is dropped
+    pub fn register_stale_listener<F>(&mut self, callback: F)
+    where
+        F: 'static + Send + FnMut(Duration),
+    {
+        self.stale_callbacks.push(Box::new(callback));
+    }
+    /// add a listener that fires when the server says whether the connection is connected, from connectionStateChanged messages
+    pub fn register_connection_state_listener<F>(&mut self, callback: F)
+    where
+        F: 'static + Send + FnMut(bool),
+    {
+        self.connection_state_callbacks.push(Box::new(callback));
+    }
+    /// add a listener that fires when a world's event server endpoint goes online or offline, from serviceStateChanged messages
+    pub fn register_service_state_listener<F>(&mut self, callback: F)
+    where
+        F: 'static + Send + FnMut(&ServiceStateChange),
+    {
+        self.service_state_callbacks.push(Box::new(callback));
+    }
 
     fn call_ps2_response_callbacks(
-        callbacks: &mut Vec<Box<dyn FnMut(&EventResponse)>>,
+        callbacks: &mut [Ps2ResponseCallback],
         response: &EventResponse,
     ) {
         for func in callbacks.iter_mut() {
-            func(&response);
+            func(response);
         }
     }
-    fn call_event_callbacks(
-        callbacks: &mut Vec<Box<dyn FnMut(&EventPayload)>>,
-        response: &EventPayload,
+    fn call_event_callbacks(callbacks: &mut [EventCallback], response: &EventPayload) {
+        for func in callbacks.iter_mut() {
+            func(response);
+        }
+    }
+    fn call_all_reponse_callbacks(callbacks: &mut [AllResponseCallback], response: &Value) {
+        for func in callbacks.iter_mut() {
+            func(response);
+        }
+    }
+    fn call_error_callbacks(
+        callbacks: &mut [ErrorCallback],
+        message: &[u8],
+        error: &serde_json::Error,
     ) {
         for func in callbacks.iter_mut() {
-            func(&response);
+            func(message, error);
         }
     }
-    fn call_all_reponse_callbacks(callbacks: &mut Vec<Box<dyn FnMut(&Value)>>, response: &Value) {
+    fn call_endpoint_status_callbacks(
+        callbacks: &mut [EndpointStatusCallback],
+        environment: Environment,
+        world: World,
+        online: bool,
+    ) {
         for func in callbacks.iter_mut() {
-            func(&response);
+            func(environment, world, online);
         }
     }
-
-    /// parse an incoming message and call the relevant callback functions
-    fn handle_message(&mut self, message: &[u8]) {
-        if self.all_callbacks.len() > 0 {
-            let basic_parse: serde_json::Value = serde_json::from_slice(&message).unwrap();
-            Self::call_all_reponse_callbacks(&mut self.all_callbacks, &basic_parse);
+    fn call_connection_state_callbacks(callbacks: &mut [ConnectionStateCallback], connected: bool) {
+        for func in callbacks.iter_mut() {
+            func(connected);
+        }
+    }
+    fn call_service_state_callbacks(
+        callbacks: &mut [ServiceStateCallback],
+        change: &ServiceStateChange,
+    ) {
+        for func in callbacks.iter_mut() {
+            func(change);
+        }
+    }
+    fn call_stale_callbacks(callbacks: &mut [StaleCallback], idle: Duration) {
+        for func in callbacks.iter_mut() {
+            func(idle);
+        }
+    }
+    fn call_subscription_callbacks(
+        callbacks: &mut [SubscriptionCallback],
+        subscriptions: &SubscriptionManager,
+    ) {
+        for func in callbacks.iter_mut() {
+            func(subscriptions);
         }
+    }
+
+    /// parse an incoming message and call the relevant callback functions, returning the message if it was a valid PS2 response
+    fn handle_message(&mut self, message: &[u8]) -> Option<EventResponse> {
+        let basic_parse: Value = match serde_json::from_slice(message) {
+            Ok(x) => x,
+            Err(e) => {
+                Self::call_error_callbacks(&mut self.error_callbacks, message, &e);
+                return None;
+            }
+        };
+        Self::call_all_reponse_callbacks(&mut self.all_callbacks, &basic_parse);
 
-        match serde_json::from_slice::<EventResponse>(message) {
+        match EventResponse::deserialize(&basic_parse) {
             Ok(resp) => {
                 Self::call_ps2_response_callbacks(&mut self.ps2_callbacks, &resp);
-                if let EventResponse::ExternallyTagged(
-                    ExternallyTaggedEventResponse::Subscription(_s),
-                ) = &resp
-                {
-                    // TODO
-                    // we have a subscription callback, we could update to say we have seen the subscription returned, but like nah
-                }
+                // subscription responses are handled by the client, as it knows what was requested
                 if let EventResponse::InternallyTagged(
                     InternallyTaggedEventResponse::ServiceMessage {
                         service: _,
@@ -102,28 +284,147 @@ impl CallbackHolder {
                     },
                 ) = &resp
                 {
+                    if let EventPayload::Unknown { raw, .. } = payload {
+                        // still passed on to the listeners, but say why it wasn't recognised
+                        if let Err(e) = EventPayload::parse_strict(raw) {
+                            Self::call_error_callbacks(&mut self.error_callbacks, message, &e);
+                        }
+                    }
                     // event callback
                     Self::call_event_callbacks(&mut self.event_callbacks, payload);
+                    for listener in self.async_event_listeners.iter_mut() {
+                        listener.push(payload.clone());
+                    }
+                }
+                if let EventResponse::InternallyTagged(InternallyTaggedEventResponse::Heartbeat {
+                    online,
+                    ..
+                }) = &resp
+                {
+                    if let Some(last) = &self.last_endpoint_status {
+                        for (environment, world, online) in online.changes_since(last) {
+                            Self::call_endpoint_status_callbacks(
+                                &mut self.endpoint_status_callbacks,
+                                environment,
+                                world,
+                                online,
+                            );
+                        }
+                    }
+                    self.last_endpoint_status = Some(online.clone());
+                }
+                if let EventResponse::InternallyTagged(
+                    InternallyTaggedEventResponse::ServiceStateChanged { online, detail, .. },
+                ) = &resp
+                {
+                    Self::call_service_state_callbacks(
+                        &mut self.service_state_callbacks,
+                        &ServiceStateChange::new(detail, *online),
+                    );
+                }
+                if let EventResponse::InternallyTagged(
+                    InternallyTaggedEventResponse::ConnectionStateChanged { connected, .. },
+                ) = &resp
+                {
+                    Self::call_connection_state_callbacks(
+                        &mut self.connection_state_callbacks,
+                        *connected,
+                    );
                 }
                 // we don't care about other message types
                 // match resp {
                 //     EventResponse::Untagged(UntaggedEventResponse::HelpResponse{..}) => (),
                 //     EventResponse::Untagged(UntaggedEventResponse::HelpInfo{..}) => (),
-                //     EventResponse::InternallyTagged(InternallyTaggedEventResponse::Heartbeat{..}) => (),
-                //     EventResponse::InternallyTagged(InternallyTaggedEventResponse::ServiceStateChanged{..}) => (),
-                //     EventResponse::InternallyTagged(InternallyTaggedEventResponse::ConnectionStateChanged{..}) => (),
                 // }
+                Some(resp)
+            }
+            Err(e) => {
+                // the error from the untagged response enum doesn't say what was wrong, so if it was an event find out why the payload didn't parse
+                let error = match basic_parse.get("payload") {
+                    Some(payload) if basic_parse["type"] == "serviceMessage" => {
+                        EventPayload::deserialize(payload).err().unwrap_or(e)
+                    }
+                    _ => e,
+                };
+                Self::call_error_callbacks(&mut self.error_callbacks, message, &error);
+                None
             }
-            Err(_) => (), // probably an echo response, just ignore it
         }
     }
+
+    /// if any async listener has futures running
+    fn async_listeners_busy(&self) -> bool {
+        self.async_event_listeners.iter().any(|l| !l.is_idle())
+    }
+
+    /// run the async listeners' futures until at least one finishes, never finishes if none are running
+    async fn drive_async_listeners(&mut self) {
+        poll_fn(|cx| {
+            let mut finished = false;
+            for listener in self.async_event_listeners.iter_mut() {
+                finished |= listener.poll_finished(cx).is_ready();
+            }
+            if finished {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// run the async listeners until everything they have queued is done
+    async fn finish_async_listeners(&mut self) {
+        while self.async_listeners_busy() {
+            self.drive_async_listeners().await;
+        }
+    }
+
+    /// the server has confirmed the subscription, and the client has recorded it
+    fn handle_subscription_update(&mut self, subscriptions: &SubscriptionManager) {
+        Self::call_subscription_callbacks(&mut self.subscription_callbacks, subscriptions);
+    }
+
+    /// the connection has been idle for too long
+    fn handle_stale_connection(&mut self, idle: Duration) {
+        Self::call_stale_callbacks(&mut self.stale_callbacks, idle);
+    }
+
+    /// report part of a message that could not be split into a JSON document
+    fn handle_malformed_message(&mut self, message: &[u8], error: &serde_json::Error) {
+        Self::call_error_callbacks(&mut self.error_callbacks, message, error);
+    }
 }
 
 pub struct EventStreamingClient {
+    environment: Environment,
     connect_url: String,
+    /// how to make the tcp and tls connection, `None` picks based on the url
+    connector: Option<Connector>,
     websocket_stream: Option<WebSocketStream<MaybeTlsStream<TcpStream>>>,
 
     callbacks: CallbackHolder,
+    /// responses that have been received and handled, but not yet returned from `next_response`
+    received_responses: VecDeque<Result<EventResponse, Error>>,
+
+    reconnect_policy: Option<ReconnectPolicy>,
+    subscriptions: SubscriptionManager,
+    /// set once reconnecting has failed, so the client doesn't keep trying forever
+    gave_up: bool,
+    /// failed reconnection attempts in a row, kept here so the backoff carries on if a reconnect is interrupted
+    reconnect_failures: u32,
+    /// set while connected but the subscriptions haven't been sent again yet, so `receive` can finish an interrupted reconnect
+    replay_pending: bool,
+    /// how many times the client has connected, so replies can be matched to the connection their request was sent on
+    connection_count: u32,
+
+    idle_timeout: Option<Duration>,
+    ping_interval: Option<Duration>,
+    /// how long `request_reply` waits for the server to answer
+    reply_timeout: Duration,
+    /// when the server last showed it was still alive, with a heartbeat or a pong
+    last_activity: Instant,
+    last_ping: Instant,
 }
 impl EventStreamingClient {
     pub fn new(environment: Environment, service_id: String, callbacks: CallbackHolder) -> Self {
@@ -132,61 +433,415 @@ impl EventStreamingClient {
             .replace("{service_id}", &service_id);
 
         Self {
+            environment,
             connect_url: url,
+            connector: Option::None,
             websocket_stream: Option::None,
 
             callbacks,
+            received_responses: VecDeque::new(),
+
+            reconnect_policy: Option::None,
+            subscriptions: SubscriptionManager::new(),
+            gave_up: false,
+            reconnect_failures: 0,
+            replay_pending: false,
+            connection_count: 0,
+
+            idle_timeout: Option::None,
+            ping_interval: Option::None,
+            reply_timeout: DEFAULT_REPLY_TIMEOUT,
+            last_activity: Instant::now(),
+            last_ping: Instant::now(),
         }
     }
 
-    pub async fn connect(&mut self) -> Result<(), Box<dyn Error>> {
+    /// connect to a different websocket url instead of the push server, like `ws://localhost:8080/streaming` for a local stand-in
+    ///
+    /// the url is used as is, so the environment and service id given to `new` are not added to it
+    pub fn with_url(mut self, url: String) -> Self {
+        self.connect_url = url;
+        self
+    }
+
+    /// make the connection with a specific connector, for example a tls connector with extra root certificates
+    pub fn with_connector(mut self, connector: Connector) -> Self {
+        self.connector = Some(connector);
+        self
+    }
+
+    /// reconnect automatically whenever the connection drops while running, then subscribe again to everything requested through `send_request`
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = Some(policy);
+        self
+    }
+
+    /// treat the connection as stale if the server sends no heartbeat (or pong, if pings are enabled) for this long
+    ///
+    /// the server sends a heartbeat every 30 seconds or so, so this should be comfortably longer than that
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// send a websocket ping this often, the pongs count as activity for the idle timeout
+    pub fn with_ping_interval(mut self, interval: Duration) -> Self {
+        self.ping_interval = Some(interval);
+        self
+    }
+
+    /// how long `recent_character_ids` and `recent_character_ids_count` wait for the reply before giving up, 30 seconds by default
+    pub fn with_reply_timeout(mut self, timeout: Duration) -> Self {
+        self.reply_timeout = timeout;
+        self
+    }
+
+    pub async fn connect(&mut self) -> Result<(), Error> {
         if self.websocket_stream.is_some() {
             return Ok(()); // already connected
         }
         let url = url::Url::parse(&self.connect_url)?;
-        let (stream, _) = connect_async(url).await?;
+        let (stream, _) =
+            connect_async_tls_with_config(url.as_str(), None, false, self.connector.clone())
+                .await?;
         self.websocket_stream = Option::from(stream);
+        self.connection_count = self.connection_count.wrapping_add(1);
+        self.last_activity = Instant::now();
+        self.last_ping = Instant::now();
         Ok(())
     }
 
-    pub async fn send_request(&mut self, request: EventRequest) -> Result<(), Box<dyn Error>> {
-        let stream = self.websocket_stream.as_mut().ok_or("planetside2 event client not connected to websocket, make sure to call 'connect' first")?;
-        let serialized = serde_json::to_string(&request)?;
+    /// send a request to the server, requests naming a world from another environment are rejected without sending anything
+    pub async fn send_request(&mut self, request: EventRequest) -> Result<(), Error> {
+        if let Some(world) = request
+            .worlds
+            .iter()
+            .flatten()
+            .find(|w| !w.is_in(self.environment))
+        {
+            return Err(Error::WrongEnvironment {
+                world: *world,
+                environment: self.environment,
+            });
+        }
+        self.send_request_unrecorded(&request).await?;
+        self.subscriptions.record_request(&request);
+        Ok(())
+    }
+
+    /// what has been subscribed to, and what the server has confirmed
+    pub fn subscriptions(&self) -> &SubscriptionManager {
+        &self.subscriptions
+    }
+
+    async fn send_request_unrecorded(&mut self, request: &EventRequest) -> Result<(), Error> {
+        let stream = self.websocket_stream.as_mut().ok_or(Error::NotConnected)?;
+        let serialized = serde_json::to_string(request)?;
         let msg: Message = Message::text(serialized);
         stream.send(msg).await?;
         Ok(())
     }
 
-    pub async fn run(mut self) {
-        let stream = match self.websocket_stream.as_mut() {
-            Some(s) => s,
-            None => {
-                return;
+    /// drop the current connection and try to establish a new one following the reconnect policy, then restore the subscriptions
+    ///
+    /// if this is cancelled part way through, the next call to `receive` picks up where it left off
+    async fn reconnect(&mut self, policy: ReconnectPolicy) -> Result<(), Error> {
+        loop {
+            tokio::time::sleep(policy.delay(self.reconnect_failures)).await;
+            match self.connect_and_resubscribe().await {
+                Ok(()) => {
+                    self.reconnect_failures = 0;
+                    return Ok(());
+                }
+                Err(e) => {
+                    self.reconnect_failures += 1;
+                    if !policy.should_retry(self.reconnect_failures) {
+                        return Err(e);
+                    }
+                }
             }
+        }
+    }
+
+    async fn connect_and_resubscribe(&mut self) -> Result<(), Error> {
+        self.websocket_stream = None;
+        self.subscriptions.reset_confirmation();
+        self.replay_pending = true;
+        self.connect().await?;
+        self.replay_subscriptions().await
+    }
+
+    /// send everything subscribed to again in one request, after connecting again
+    async fn replay_subscriptions(&mut self) -> Result<(), Error> {
+        if let Some(request) = self.subscriptions.replay_request() {
+            self.send_request_unrecorded(&request).await?;
+        }
+        self.replay_pending = false;
+        Ok(())
+    }
+
+    /// wait for the next valid PS2 response from the server, calling the registered callbacks on the way
+    ///
+    /// returns `None` once the connection has closed, or once reconnecting has failed if a reconnect policy is set
+    pub async fn next_response(&mut self) -> Option<Result<EventResponse, Error>> {
+        loop {
+            if let Some(resp) = self.received_responses.pop_front() {
+                return Some(resp);
+            }
+            if let Err(e) = self.receive().await? {
+                return Some(Err(e));
+            }
+        }
+    }
+
+    /// read the next websocket message from the server, adding everything in it to `received_responses`
+    ///
+    /// returns `None` once the connection has closed, or once reconnecting has failed if a reconnect policy is set
+    async fn receive(&mut self) -> Option<Result<(), Error>> {
+        if self.replay_pending && self.websocket_stream.is_some() {
+            // a reconnect was interrupted after connecting, the new connection has no subscriptions yet
+            let replayed = self.replay_subscriptions().await;
+            if replayed.is_err() {
+                // start again with a fresh connection rather than retrying on a broken one
+                self.websocket_stream = None;
+            }
+            return Some(replayed);
+        }
+        let stale_at = self.idle_timeout.map(|t| self.last_activity + t);
+        let ping_at = self.ping_interval.map(|p| self.last_ping + p);
+        let msg = match self.websocket_stream.as_mut() {
+            Some(stream) => tokio::select! {
+                msg = stream.next() => msg,
+                _ = self.callbacks.drive_async_listeners(), if self.callbacks.async_listeners_busy() => {
+                    return Some(Ok(()));
+                }
+                _ = sleep_until(stale_at) => {
+                    let idle = self.last_activity.elapsed();
+                    // drop the connection, so it is either re-established or the client stops
+                    self.websocket_stream = None;
+                    self.callbacks.handle_stale_connection(idle);
+                    return Some(Err(Error::Stale { idle }));
+                }
+                _ = sleep_until(ping_at) => {
+                    self.last_ping = Instant::now();
+                    let ping = stream.send(Message::Ping(Default::default())).await;
+                    return Some(ping.map_err(Error::from));
+                }
+            },
+            None => None,
         };
-        while let Some(msg) = stream.next().await {
-            match msg {
-                Ok(msg) => {
-                    if msg.is_text() {
-                        let msg_text = msg.into_data();
-
-                        // sometimes a response contains multiple messages stuck together, split them up and handle each one in order
-                        let mut msg_iter = msg_text.iter().enumerate();
-                        let mut last = 0;
-                        while let Some(position) = msg_iter.position(|(i, &x)| {
-                            x == ('}' as u8)
-                                && (msg_text.get(i + 1) == Some(&('{' as u8))
-                                    || i + 1 == msg_text.len())
-                        }) {
-                            if let Some(short_msg) = msg_text.get(last..(last + position + 1)) {
-                                self.callbacks.handle_message(&short_msg);
-                            }
-                            last += position + 1;
+        match msg {
+            Some(Ok(msg)) => {
+                if msg.is_text() {
+                    self.handle_text(msg.into_data().as_ref());
+                } else if msg.is_pong() && self.ping_interval.is_some() {
+                    self.last_activity = Instant::now();
+                }
+                Some(Ok(()))
+            }
+            Some(Err(e)) => {
+                if self.reconnect_policy.is_some() {
+                    // the connection is probably broken, start again next time
+                    self.websocket_stream = None;
+                }
+                Some(Err(e.into()))
+            }
+            None => {
+                self.websocket_stream = None;
+                let policy = match self.reconnect_policy {
+                    Some(p) if !self.gave_up => p,
+                    _ => return None,
+                };
+                if let Err(e) = self.reconnect(policy).await {
+                    self.gave_up = true;
+                    return Some(Err(e));
+                }
+                Some(Ok(()))
+            }
+        }
+    }
+
+    /// send a request and wait for the reply, any other responses received while waiting are still returned from `next_response` later
+    ///
+    /// gives up with [`Error::ReplyTimeout`] if the reply doesn't arrive within the reply timeout, a reconnect still in progress then is finished by the next `receive`
+    async fn request_reply<T>(
+        &mut self,
+        request: EventRequest,
+        reply: impl FnMut(&EventResponse) -> Option<T>,
+    ) -> Result<T, Error> {
+        let timeout = self.reply_timeout;
+        tokio::time::timeout(timeout, self.wait_for_reply(request, reply))
+            .await
+            .map_err(|_| Error::ReplyTimeout(timeout))?
+    }
+
+    async fn wait_for_reply<T>(
+        &mut self,
+        request: EventRequest,
+        mut reply: impl FnMut(&EventResponse) -> Option<T>,
+    ) -> Result<T, Error> {
+        // anything already received can't be the reply
+        let mut checked = self.received_responses.len();
+        self.send_request(request).await?;
+        let connection = self.connection_count;
+        loop {
+            while let Some(resp) = self.received_responses.get(checked) {
+                if let Some(value) = resp.as_ref().ok().and_then(&mut reply) {
+                    self.received_responses.remove(checked);
+                    return Ok(value);
+                }
+                checked += 1;
+            }
+            self.receive().await.ok_or(Error::ConnectionClosed)??;
+            if self.connection_count != connection {
+                // the reply was lost with the old connection
+                return Err(Error::ConnectionClosed);
+            }
+        }
+    }
+
+    /// ask the server for the characters that have recently been online, and wait for the reply
+    ///
+    /// everything else received while waiting is kept for `next_response`, so when only calling this the other responses need draining with `next_response` to stop them piling up
+    pub async fn recent_character_ids(&mut self) -> Result<Vec<Character>, Error> {
+        self.request_reply(EventRequest::recent_character_ids(), |resp| match resp {
+            EventResponse::ExternallyTagged(ExternallyTaggedEventResponse::RecentCharacterIds(
+                ids,
+            )) => Some(ids.0.clone()),
+            _ => None,
+        })
+        .await
+    }
+
+    /// ask the server for the number of characters that have recently been online, and wait for the reply
+    ///
+    /// like [`EventStreamingClient::recent_character_ids`], other responses received while waiting are kept for `next_response`
+    pub async fn recent_character_ids_count(&mut self) -> Result<u32, Error> {
+        self.request_reply(
+            EventRequest::recent_character_ids_count(),
+            |resp| match resp {
+                EventResponse::ExternallyTagged(
+                    ExternallyTaggedEventResponse::RecentCharacterIdsCount(count),
+                ) => Some(count.0),
+                _ => None,
+            },
+        )
+        .await
+    }
+
+    /// handle messages from the server until the connection closes, or until reconnecting fails if a reconnect policy is set
+    ///
+    /// returns the error from the last reconnect attempt if the client gave up reconnecting, any other error is skipped and the client carries on with the next message
+    pub async fn run(mut self) -> Result<(), Error> {
+        let mut result = Ok(());
+        while let Some(resp) = self.next_response().await {
+            if let Err(e) = resp {
+                if self.gave_up {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        self.callbacks.finish_async_listeners().await;
+        result
+    }
+
+    /// a stream of every valid PS2 response from the server, the registered callbacks are still called as each response arrives
+    ///
+    /// the stream only borrows the client, so once it is dropped the client can be used again, to change the subscription with `send_request` for example
+    pub fn stream(&mut self) -> BoxStream<'_, Result<EventResponse, Error>> {
+        futures_util::stream::unfold(self, |client| async move {
+            let resp = client.next_response().await;
+            if resp.is_none() {
+                client.callbacks.finish_async_listeners().await;
+            }
+            resp.map(|resp| (resp, client))
+        })
+        .boxed()
+    }
+
+    /// a stream of only the event payloads from the server, any errors are skipped
+    ///
+    /// like [`EventStreamingClient::stream`], the client can be used again once the stream is dropped
+    pub fn event_stream(&mut self) -> BoxStream<'_, EventPayload> {
+        self.stream()
+            .filter_map(|resp| async move { event_payload(resp) })
+            .boxed()
+    }
+
+    /// turn the client into a stream of every valid PS2 response from the server, for moving to another task
+    ///
+    /// the client can't be used for anything else afterwards, [`EventStreamingClient::stream`] borrows it instead
+    pub fn into_stream(self) -> BoxStream<'static, Result<EventResponse, Error>> {
+        futures_util::stream::unfold(self, |mut client| async move {
+            let resp = client.next_response().await;
+            if resp.is_none() {
+                client.callbacks.finish_async_listeners().await;
+            }
+            resp.map(|resp| (resp, client))
+        })
+        .boxed()
+    }
+
+    /// turn the client into a stream of only the event payloads from the server, any errors are skipped
+    pub fn into_event_stream(self) -> BoxStream<'static, EventPayload> {
+        self.into_stream()
+            .filter_map(|resp| async move { event_payload(resp) })
+            .boxed()
+    }
+
+    fn handle_text(&mut self, msg_text: &[u8]) {
+        // sometimes a response contains multiple messages stuck together, split them up and handle each one in order
+        for frame in framing::split_frames(msg_text) {
+            match frame {
+                Ok(frame) => {
+                    if let Some(resp) = self.callbacks.handle_message(frame) {
+                        if let EventResponse::ExternallyTagged(
+                            ExternallyTaggedEventResponse::Subscription(info),
+                        ) = &resp
+                        {
+                            self.subscriptions.record_confirmation(info);
+                            self.callbacks
+                                .handle_subscription_update(&self.subscriptions);
                         }
+                        if let EventResponse::InternallyTagged(
+                            InternallyTaggedEventResponse::Heartbeat { .. },
+                        ) = &resp
+                        {
+                            self.last_activity = Instant::now();
+                        }
+                        self.received_responses.push_back(Ok(resp));
                     }
                 }
-                Err(_) => (), // just ignore the error
+                Err(malformed) => {
+                    self.callbacks
+                        .handle_malformed_message(malformed.bytes, &malformed.error);
+                    self.received_responses
+                        .push_back(Err(Error::MalformedMessage {
+                            message: malformed.bytes.to_vec(),
+                            source: malformed.error,
+                        }));
+                }
             }
         }
     }
 }
+
+/// the payload of a response, if it is an event
+fn event_payload(resp: Result<EventResponse, Error>) -> Option<EventPayload> {
+    match resp {
+        Ok(EventResponse::InternallyTagged(InternallyTaggedEventResponse::ServiceMessage {
+            payload,
+            ..
+        })) => Some(payload),
+        _ => None,
+    }
+}
+
+/// sleep until the deadline, or forever if there isn't one
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
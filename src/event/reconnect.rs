@@ -0,0 +1,54 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// how the client should try to get back online after the connection to the push server drops
+#[derive(Debug, Clone, Copy)]
+pub struct ReconnectPolicy {
+    /// how long to wait before the first reconnection attempt
+    pub initial_delay: Duration,
+    /// the longest the client will ever wait between two attempts
+    pub max_delay: Duration,
+    /// how much the delay grows after each failed attempt
+    pub multiplier: f64,
+    /// the fraction of each delay that is randomised, between 0 and 1, so many clients don't reconnect in lockstep
+    pub jitter: f64,
+    /// how many failed attempts in a row to allow before giving up, `None` to keep trying forever
+    pub max_retries: Option<u32>,
+}
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(60),
+            multiplier: 2.0,
+            jitter: 0.5,
+            max_retries: Some(10),
+        }
+    }
+}
+impl ReconnectPolicy {
+    /// the time to wait before making the given attempt, counting from 0
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = i32::try_from(attempt).unwrap_or(i32::MAX);
+        let base = (self.initial_delay.as_secs_f64() * self.multiplier.powi(exponent))
+            .min(self.max_delay.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        Duration::from_secs_f64(base * (1.0 - jitter * random_fraction()))
+    }
+
+    /// if another attempt should be made after `failures` attempts in a row have failed
+    pub fn should_retry(&self, failures: u32) -> bool {
+        match self.max_retries {
+            Some(max) => failures < max,
+            None => true,
+        }
+    }
+}
+
+/// a number between 0 and 1, random enough to spread out reconnection attempts
+fn random_fraction() -> f64 {
+    // each RandomState is seeded with fresh random keys, which saves pulling in a rng crate just for this
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,409 @@
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use crate::data::{Character, World};
+
+use super::message::{
+    EventRequest, EventRequestAction, SubscriptionInfoEventResponse, SubscriptionRequestBuilder,
+};
+use super::EventType;
+
+/// the characters, worlds and events a client is subscribed to
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Subscription {
+    /// subscribed to events for every character, rather than just the ones in `characters`
+    pub all_characters: bool,
+    pub characters: BTreeSet<Character>,
+    pub worlds: BTreeSet<World>,
+    pub event_names: BTreeSet<EventType>,
+    /// if events must match both a subscribed character and a subscribed world, see [`SubscriptionRequestBuilder::logical_and_characters_with_worlds`]
+    pub logical_and_characters_with_worlds: bool,
+}
+impl Subscription {
+    /// if nothing is subscribed to, `logical_and_characters_with_worlds` doesn't count as it doesn't subscribe to anything by itself
+    pub fn is_empty(&self) -> bool {
+        !self.all_characters
+            && self.characters.is_empty()
+            && self.worlds.is_empty()
+            && self.event_names.is_empty()
+    }
+
+    /// a subscribe request for everything in the subscription
+    pub fn to_request(&self) -> EventRequest {
+        self.fill(EventRequest::subscribe())
+            .logical_and_characters_with_worlds(self.logical_and_characters_with_worlds)
+            .build()
+    }
+
+    /// the smallest set of requests that changes a connection subscribed to `self` into one subscribed to `target`
+    ///
+    /// anything that is no longer wanted is cleared first, then anything new is subscribed to, along with `logical_and_characters_with_worlds` if it changes
+    pub fn diff(&self, target: &Subscription) -> Vec<EventRequest> {
+        if target.is_empty() {
+            return if self.is_empty() {
+                vec![]
+            } else {
+                vec![EventRequest::clear_all()]
+            };
+        }
+
+        let removed = Subscription {
+            all_characters: self.all_characters && !target.all_characters,
+            characters: &self.characters - &target.characters,
+            worlds: &self.worlds - &target.worlds,
+            event_names: &self.event_names - &target.event_names,
+            logical_and_characters_with_worlds: false,
+        };
+        let added = Subscription {
+            all_characters: target.all_characters && !self.all_characters,
+            characters: &target.characters - &self.characters,
+            worlds: &target.worlds - &self.worlds,
+            event_names: &target.event_names - &self.event_names,
+            logical_and_characters_with_worlds: target.logical_and_characters_with_worlds,
+        };
+
+        let mut requests = vec![];
+        if !removed.is_empty() {
+            requests.push(removed.fill(EventRequest::clear_subscribe()).build());
+        }
+        if !added.is_empty()
+            || self.logical_and_characters_with_worlds != target.logical_and_characters_with_worlds
+        {
+            requests.push(added.to_request());
+        }
+        requests
+    }
+
+    fn fill(&self, builder: SubscriptionRequestBuilder) -> SubscriptionRequestBuilder {
+        let builder = builder
+            .characters(self.characters.iter().copied())
+            .worlds(self.worlds.iter().copied())
+            .events(self.event_names.iter().copied());
+        if self.all_characters {
+            builder.all_characters()
+        } else {
+            builder
+        }
+    }
+
+    /// add everything in a subscribe request
+    fn add(&mut self, request: &EventRequest) {
+        for character in request.characters.iter().flatten() {
+            match parse_character(character) {
+                Some(c) => {
+                    self.characters.insert(c);
+                }
+                None => self.all_characters = true,
+            }
+        }
+        self.worlds.extend(request.worlds.iter().flatten());
+        self.event_names
+            .extend(request.event_names.iter().flatten());
+        if let Some(logical_and) = request.logical_and_characters_with_worlds {
+            self.logical_and_characters_with_worlds = logical_and;
+        }
+    }
+
+    /// remove everything in a clearSubscribe request
+    fn remove(&mut self, request: &EventRequest) {
+        for character in request.characters.iter().flatten() {
+            match parse_character(character) {
+                Some(c) => {
+                    self.characters.remove(&c);
+                }
+                None => self.all_characters = false,
+            }
+        }
+        for world in request.worlds.iter().flatten() {
+            self.worlds.remove(world);
+        }
+        for event in request.event_names.iter().flatten() {
+            self.event_names.remove(event);
+        }
+    }
+}
+
+/// parses a character id from a request, `None` means "all"
+fn parse_character(character: &str) -> Option<Character> {
+    Character::from_str(character).ok()
+}
+
+/// a difference between what was requested and what the server confirmed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubscriptionMismatch {
+    /// a world was requested, but the server did not confirm it
+    MissingWorld(World),
+    /// the server confirmed a world that was not requested
+    UnexpectedWorld(World),
+    /// an event was requested, but the server did not confirm it
+    MissingEvent(EventType),
+    /// the server confirmed an event that was not requested
+    UnexpectedEvent(EventType),
+    /// a character was requested, but the server did not confirm it, only checked when the server lists the characters
+    MissingCharacter(Character),
+    /// the server confirmed a character that was not requested, only checked when the server lists the characters
+    UnexpectedCharacter(Character),
+    /// the server is subscribed to a different number of characters than were requested
+    CharacterCount { requested: usize, confirmed: u32 },
+    /// the server is combining characters and worlds differently than was requested
+    LogicalAndCharactersWithWorlds { requested: bool, confirmed: bool },
+}
+
+/// keeps track of the subscriptions that have been requested, and what the server has confirmed in its subscription responses
+#[derive(Debug, Clone, Default)]
+pub struct SubscriptionManager {
+    requested: Subscription,
+
+    confirmed: Option<Subscription>,
+    confirmed_character_count: Option<u32>,
+    /// if the last confirmation listed every character, or just gave a count
+    confirmed_characters_listed: bool,
+}
+impl SubscriptionManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// everything that has been requested since the last time everything was cleared
+    pub fn requested(&self) -> &Subscription {
+        &self.requested
+    }
+
+    /// the current effective subscription, as last confirmed by the server, `None` until the first confirmation arrives
+    pub fn effective(&self) -> Option<&Subscription> {
+        self.confirmed.as_ref()
+    }
+
+    /// the number of characters the server last confirmed
+    pub fn confirmed_character_count(&self) -> Option<u32> {
+        self.confirmed_character_count
+    }
+
+    /// everything that differs between what was requested and what the server last confirmed, empty until the first confirmation arrives
+    pub fn mismatches(&self) -> Vec<SubscriptionMismatch> {
+        let confirmed = match &self.confirmed {
+            Some(c) => c,
+            None => return vec![],
+        };
+        let requested = &self.requested;
+
+        let mut mismatches = vec![];
+        if requested.logical_and_characters_with_worlds
+            != confirmed.logical_and_characters_with_worlds
+        {
+            mismatches.push(SubscriptionMismatch::LogicalAndCharactersWithWorlds {
+                requested: requested.logical_and_characters_with_worlds,
+                confirmed: confirmed.logical_and_characters_with_worlds,
+            });
+        }
+        mismatches.extend(
+            requested
+                .worlds
+                .difference(&confirmed.worlds)
+                .map(|w| SubscriptionMismatch::MissingWorld(*w)),
+        );
+        mismatches.extend(
+            confirmed
+                .worlds
+                .difference(&requested.worlds)
+                .map(|w| SubscriptionMismatch::UnexpectedWorld(*w)),
+        );
+        mismatches.extend(
+            requested
+                .event_names
+                .difference(&confirmed.event_names)
+                .map(|e| SubscriptionMismatch::MissingEvent(*e)),
+        );
+        mismatches.extend(
+            confirmed
+                .event_names
+                .difference(&requested.event_names)
+                .map(|e| SubscriptionMismatch::UnexpectedEvent(*e)),
+        );
+        if self.confirmed_characters_listed {
+            mismatches.extend(
+                requested
+                    .characters
+                    .difference(&confirmed.characters)
+                    .map(|c| SubscriptionMismatch::MissingCharacter(*c)),
+            );
+            mismatches.extend(
+                confirmed
+                    .characters
+                    .difference(&requested.characters)
+                    .map(|c| SubscriptionMismatch::UnexpectedCharacter(*c)),
+            );
+        }
+        if let Some(count) = self.confirmed_character_count {
+            // the count doesn't mean much when subscribed to every character
+            if !requested.all_characters && count as usize != requested.characters.len() {
+                mismatches.push(SubscriptionMismatch::CharacterCount {
+                    requested: requested.characters.len(),
+                    confirmed: count,
+                });
+            }
+        }
+        mismatches
+    }
+
+    /// the request to send to restore the subscription on a new connection, `None` if nothing is subscribed
+    pub(crate) fn replay_request(&self) -> Option<EventRequest> {
+        if self.requested.is_empty() {
+            None
+        } else {
+            Some(self.requested.to_request())
+        }
+    }
+
+    /// update the requested subscription from a request sent to the server
+    pub(crate) fn record_request(&mut self, request: &EventRequest) {
+        match request.action {
+            EventRequestAction::ClearSubscribe if request.all == Some(true) => {
+                self.requested = Subscription::default();
+            }
+            EventRequestAction::ClearSubscribe => self.requested.remove(request),
+            EventRequestAction::Subscribe => self.requested.add(request),
+            _ => (),
+        }
+    }
+
+    /// update the confirmed subscription from a subscription response from the server
+    pub(crate) fn record_confirmation(&mut self, info: &SubscriptionInfoEventResponse) {
+        let mut confirmed = Subscription {
+            event_names: info.event_names.iter().copied().collect(),
+            worlds: info.worlds.iter().copied().collect(),
+            logical_and_characters_with_worlds: info.logical_and_characters_with_worlds,
+            ..Default::default()
+        };
+        for character in info.characters.iter().flatten() {
+            match parse_character(character) {
+                Some(c) => {
+                    confirmed.characters.insert(c);
+                }
+                None => confirmed.all_characters = true,
+            }
+        }
+        self.confirmed = Some(confirmed);
+        self.confirmed_character_count = info.character_count.map(|c| c.max(0) as u32);
+        self.confirmed_characters_listed = info.characters.is_some();
+    }
+
+    /// forget what the server confirmed, as a new connection starts with nothing subscribed
+    pub(crate) fn reset_confirmation(&mut self) {
+        self.confirmed = None;
+        self.confirmed_character_count = None;
+        self.confirmed_characters_listed = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscription(characters: &[u64], worlds: &[World], events: &[EventType]) -> Subscription {
+        Subscription {
+            characters: characters.iter().map(|c| Character(*c)).collect(),
+            worlds: worlds.iter().copied().collect(),
+            event_names: events.iter().copied().collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn no_change_sends_nothing() {
+        let current = subscription(&[1], &[World::Miller], &[EventType::Death]);
+        assert!(current.diff(&current.clone()).is_empty());
+        assert!(Subscription::default()
+            .diff(&Subscription::default())
+            .is_empty());
+    }
+
+    #[test]
+    fn empty_target_clears_everything() {
+        let current = subscription(&[1], &[World::Miller], &[EventType::Death]);
+        let requests = current.diff(&Subscription::default());
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].action, EventRequestAction::ClearSubscribe);
+        assert_eq!(requests[0].all, Some(true));
+    }
+
+    #[test]
+    fn removes_then_adds() {
+        let current = subscription(&[1, 2], &[World::Miller], &[EventType::Death]);
+        let target = subscription(&[2, 3], &[World::Miller], &[EventType::Death]);
+        let requests = current.diff(&target);
+        assert_eq!(requests.len(), 2);
+
+        assert_eq!(requests[0].action, EventRequestAction::ClearSubscribe);
+        assert_eq!(requests[0].all, None);
+        assert_eq!(requests[0].characters, Some(vec!["1".to_owned()]));
+        assert_eq!(requests[0].worlds, None);
+        assert_eq!(requests[0].event_names, None);
+
+        assert_eq!(requests[1].action, EventRequestAction::Subscribe);
+        assert_eq!(requests[1].characters, Some(vec!["3".to_owned()]));
+        assert_eq!(requests[1].worlds, None);
+        assert_eq!(requests[1].event_names, None);
+    }
+
+    #[test]
+    fn adding_and_removing_all_characters() {
+        let some = subscription(&[1], &[], &[EventType::Death]);
+        let all = Subscription {
+            all_characters: true,
+            ..some.clone()
+        };
+
+        let requests = some.diff(&all);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].action, EventRequestAction::Subscribe);
+        assert_eq!(requests[0].characters, Some(vec!["all".to_owned()]));
+
+        let requests = all.diff(&some);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].action, EventRequestAction::ClearSubscribe);
+        assert_eq!(requests[0].characters, Some(vec!["all".to_owned()]));
+    }
+
+    #[test]
+    fn flag_only_change() {
+        let current = subscription(&[1], &[World::Miller], &[EventType::Death]);
+        let target = Subscription {
+            logical_and_characters_with_worlds: true,
+            ..current.clone()
+        };
+
+        let requests = current.diff(&target);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].action, EventRequestAction::Subscribe);
+        assert_eq!(requests[0].logical_and_characters_with_worlds, Some(true));
+        assert_eq!(requests[0].characters, None);
+        assert_eq!(requests[0].worlds, None);
+        assert_eq!(requests[0].event_names, None);
+
+        let requests = target.diff(&current);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].logical_and_characters_with_worlds, Some(false));
+    }
+
+    #[test]
+    fn diff_applied_gives_the_target() {
+        let current = subscription(
+            &[1, 2],
+            &[World::Miller, World::Emerald],
+            &[EventType::Death],
+        );
+        let target = Subscription {
+            all_characters: true,
+            logical_and_characters_with_worlds: true,
+            ..subscription(&[2], &[World::Cobalt], &[EventType::PlayerLogin])
+        };
+
+        let mut manager = SubscriptionManager::new();
+        manager.record_request(&current.to_request());
+        for request in current.diff(&target) {
+            manager.record_request(&request);
+        }
+        assert_eq!(manager.requested(), &target);
+    }
+}
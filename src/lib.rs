@@ -1,3 +1,8 @@
 pub mod census;
 pub mod data;
+pub mod error;
 pub mod event;
+#[cfg(feature = "testing")]
+pub mod testing;
+
+pub use error::Error;
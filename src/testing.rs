@@ -0,0 +1,378 @@
+//! an in-process stand-in for the push server, for testing [`EventStreamingClient`](crate::event::EventStreamingClient) and event handlers without the live service
+//!
+//! ```ignore
+//! let server = MockPushServer::start().await?;
+//! let mut client = EventStreamingClient::new(Environment::PC, "example".to_owned(), callbacks)
+//!     .with_url(server.url());
+//! client.connect().await?;
+//! server.send_event(json!({"event_name": "PlayerLogin", /* ... */}));
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Map, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Interval;
+use tokio_tungstenite::{accept_async, tungstenite::protocol::Message, WebSocketStream};
+
+use crate::data::{Character, World};
+
+/// something for the server to do, sent from the test to the connection
+enum Command {
+    Send(String),
+    Heartbeat,
+    SetHeartbeatInterval(Option<Duration>),
+    SetOnline(World, bool),
+    SetRecentCharacters(Vec<Character>),
+    Disconnect,
+}
+
+/// a websocket server that answers requests the way the push server does, and sends whatever the test tells it to
+///
+/// it handles one connection at a time, once a connection closes the next one is accepted, so reconnecting works too
+///
+/// anything sent while no client is connected is held until the next one connects
+pub struct MockPushServer {
+    url: String,
+    commands: mpsc::UnboundedSender<Command>,
+    requests: Arc<Mutex<Vec<Value>>>,
+    task: JoinHandle<()>,
+}
+impl MockPushServer {
+    /// start listening on a free local port, heartbeats are off until [`MockPushServer::set_heartbeat_interval`] is called
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("ws://{}/streaming", listener.local_addr()?);
+        let (commands, receiver) = mpsc::unbounded_channel();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let task = tokio::spawn(run(listener, receiver, requests.clone()));
+        Ok(MockPushServer {
+            url,
+            commands,
+            requests,
+            task,
+        })
+    }
+
+    /// the `ws://` url to give to [`EventStreamingClient::with_url`](crate::event::EventStreamingClient::with_url)
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// every request the server has received so far, across all connections
+    pub fn requests(&self) -> Vec<Value> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// send an event payload, wrapped in a `serviceMessage`
+    pub fn send_event(&self, payload: Value) {
+        self.send_json(&service_message(payload));
+    }
+
+    /// send several event payloads in one websocket message, the way the real server sometimes does
+    pub fn send_events_concatenated(&self, payloads: impl IntoIterator<Item = Value>) {
+        let text = payloads
+            .into_iter()
+            .map(|payload| service_message(payload).to_string())
+            .collect();
+        self.send_raw(text);
+    }
+
+    /// send any JSON message
+    pub fn send_json(&self, message: &Value) {
+        self.send_raw(message.to_string());
+    }
+
+    /// send the text exactly as it is, which doesn't have to be valid JSON
+    pub fn send_raw(&self, text: String) {
+        self.command(Command::Send(text));
+    }
+
+    /// send a heartbeat now
+    pub fn send_heartbeat(&self) {
+        self.command(Command::Heartbeat);
+    }
+
+    /// send a heartbeat this often, `None` stops them
+    pub fn set_heartbeat_interval(&self, interval: Option<Duration>) {
+        self.command(Command::SetHeartbeatInterval(interval));
+    }
+
+    /// mark a world's endpoint as online or offline, sending a `serviceStateChanged` and changing later heartbeats
+    ///
+    /// every PC world starts online
+    pub fn set_online(&self, world: World, online: bool) {
+        self.command(Command::SetOnline(world, online));
+    }
+
+    /// the characters sent back for recentCharacterIds and recentCharacterIdsCount requests
+    pub fn set_recent_characters(&self, characters: Vec<Character>) {
+        self.command(Command::SetRecentCharacters(characters));
+    }
+
+    /// close the current connection
+    pub fn disconnect(&self) {
+        self.command(Command::Disconnect);
+    }
+
+    fn command(&self, command: Command) {
+        // the server task only stops when this is dropped
+        let _ = self.commands.send(command);
+    }
+}
+impl Drop for MockPushServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// the state that lasts between connections
+struct ServerState {
+    requests: Arc<Mutex<Vec<Value>>>,
+    heartbeat: Option<Interval>,
+    online: BTreeMap<World, bool>,
+    recent_characters: Vec<Character>,
+}
+
+/// what a connection is subscribed to, as the strings from the requests
+#[derive(Default)]
+struct ConnectionSubscription {
+    characters: BTreeSet<String>,
+    worlds: BTreeSet<String>,
+    event_names: BTreeSet<String>,
+    logical_and_characters_with_worlds: bool,
+}
+
+async fn run(
+    listener: TcpListener,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    requests: Arc<Mutex<Vec<Value>>>,
+) {
+    let mut state = ServerState {
+        requests,
+        heartbeat: None,
+        online: World::ALL_WORLDS.iter().map(|w| (*w, true)).collect(),
+        recent_characters: Vec::new(),
+    };
+    loop {
+        let Ok((stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let Ok(websocket) = accept_async(stream).await else {
+            continue;
+        };
+        if !serve(websocket, &mut commands, &mut state).await {
+            return;
+        }
+    }
+}
+
+/// handle one connection until it closes, returns `false` once the [`MockPushServer`] has been dropped
+async fn serve(
+    mut websocket: WebSocketStream<TcpStream>,
+    commands: &mut mpsc::UnboundedReceiver<Command>,
+    state: &mut ServerState,
+) -> bool {
+    let mut subscription = ConnectionSubscription::default();
+    let greeting = [
+        json!({"connected": "true", "service": "push", "type": "connectionStateChanged"}),
+        help_info(),
+    ];
+    for message in greeting {
+        if send(&mut websocket, message.to_string()).await.is_err() {
+            return true;
+        }
+    }
+
+    loop {
+        let outgoing = tokio::select! {
+            incoming = websocket.next() => match incoming {
+                Some(Ok(Message::Text(text))) => {
+                    match serde_json::from_str::<Value>(&text) {
+                        Ok(request) => {
+                            state.requests.lock().unwrap().push(request.clone());
+                            reply(&request, &mut subscription, state)
+                        }
+                        Err(_) => help_info(),
+                    }
+                    .to_string()
+                }
+                // tungstenite answers pings itself
+                Some(Ok(_)) => continue,
+                Some(Err(_)) | None => return true,
+            },
+            command = commands.recv() => match command {
+                Some(Command::Send(text)) => text,
+                Some(Command::Heartbeat) => heartbeat(&state.online).to_string(),
+                Some(Command::SetHeartbeatInterval(interval)) => {
+                    state.heartbeat = interval.map(tokio::time::interval);
+                    continue;
+                }
+                Some(Command::SetOnline(world, online)) => {
+                    state.online.insert(world, online);
+                    json!({
+                        "detail": endpoint_name(world),
+                        "online": online.to_string(),
+                        "service": "event",
+                        "type": "serviceStateChanged",
+                    })
+                    .to_string()
+                }
+                Some(Command::SetRecentCharacters(characters)) => {
+                    state.recent_characters = characters;
+                    continue;
+                }
+                Some(Command::Disconnect) => {
+                    let _ = websocket.close(None).await;
+                    return true;
+                }
+                None => return false,
+            },
+            _ = tick(&mut state.heartbeat) => heartbeat(&state.online).to_string(),
+        };
+        if send(&mut websocket, outgoing).await.is_err() {
+            return true;
+        }
+    }
+}
+
+async fn send(
+    websocket: &mut WebSocketStream<TcpStream>,
+    text: String,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    websocket.send(Message::text(text)).await
+}
+
+/// waits for the next heartbeat, or forever if they are turned off
+async fn tick(heartbeat: &mut Option<Interval>) {
+    match heartbeat {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// the reply the push server would send to a request
+fn reply(request: &Value, subscription: &mut ConnectionSubscription, state: &ServerState) -> Value {
+    if request["service"] != "event" {
+        return help_info();
+    }
+    match request["action"].as_str() {
+        Some("subscribe") => {
+            subscription
+                .characters
+                .extend(strings(&request["characters"]));
+            subscription.worlds.extend(strings(&request["worlds"]));
+            subscription
+                .event_names
+                .extend(strings(&request["eventNames"]));
+            if let Some(logical_and) = request["logicalAndCharactersWithWorlds"].as_bool() {
+                subscription.logical_and_characters_with_worlds = logical_and;
+            }
+            subscription_info(subscription, request)
+        }
+        Some("clearSubscribe") => {
+            if request["all"].as_bool() == Some(true) {
+                *subscription = ConnectionSubscription::default();
+            } else {
+                for character in strings(&request["characters"]) {
+                    subscription.characters.remove(&character);
+                }
+                for world in strings(&request["worlds"]) {
+                    subscription.worlds.remove(&world);
+                }
+                for event in strings(&request["eventNames"]) {
+                    subscription.event_names.remove(&event);
+                }
+            }
+            subscription_info(subscription, request)
+        }
+        Some("echo") => request["payload"].clone(),
+        Some("help") => json!({
+            "example event service message payloads": {},
+            "example messages to event service": {},
+        }),
+        Some("recentCharacterIds") => json!({
+            "recent_character_id_list": state
+                .recent_characters
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>(),
+        }),
+        Some("recentCharacterIdsCount") => json!({
+            "recent_character_id_count": state.recent_characters.len(),
+        }),
+        _ => help_info(),
+    }
+}
+
+/// the list in a request field as strings, as ids can be sent as either strings or numbers
+fn strings(list: &Value) -> Vec<String> {
+    list.as_array()
+        .into_iter()
+        .flatten()
+        .map(|item| match item {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        })
+        .collect()
+}
+
+fn subscription_info(subscription: &ConnectionSubscription, request: &Value) -> Value {
+    let mut info = Map::new();
+    info.insert(
+        "characterCount".to_owned(),
+        subscription.characters.len().into(),
+    );
+    if request["list_characters"].as_bool() == Some(true) {
+        info.insert(
+            "characters".to_owned(),
+            subscription.characters.iter().cloned().collect(),
+        );
+    }
+    info.insert(
+        "eventNames".to_owned(),
+        subscription.event_names.iter().cloned().collect(),
+    );
+    info.insert(
+        "logicalAndCharactersWithWorlds".to_owned(),
+        subscription.logical_and_characters_with_worlds.into(),
+    );
+    info.insert(
+        "worlds".to_owned(),
+        subscription.worlds.iter().cloned().collect(),
+    );
+    json!({ "subscription": info })
+}
+
+fn service_message(payload: Value) -> Value {
+    json!({"payload": payload, "service": "event", "type": "serviceMessage"})
+}
+
+fn heartbeat(online: &BTreeMap<World, bool>) -> Value {
+    let online: Map<String, Value> = online
+        .iter()
+        .map(|(world, online)| (endpoint_name(*world), online.to_string().into()))
+        .collect();
+    json!({"online": online, "service": "event", "type": "heartbeat"})
+}
+
+/// sent when the server gets something it doesn't understand
+fn help_info() -> Value {
+    json!({"send this for help": {"service": "event", "action": "help"}})
+}
+
+fn endpoint_name(world: World) -> String {
+    format!(
+        "EventServerEndpoint_{:?}_{}",
+        world,
+        world.id().unwrap_or_default()
+    )
+}
@@ -0,0 +1,312 @@
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use planetside2_event_client::{
+    data::{Character, Environment, World},
+    event::{
+        message::{
+            EventRequest, EventResponse, ExternallyTaggedEventResponse,
+            InternallyTaggedEventResponse,
+        },
+        CallbackHolder, EventPayload, EventStreamingClient, EventType, ReconnectPolicy,
+    },
+    testing::MockPushServer,
+    Error,
+};
+use serde_json::{json, Value};
+
+/// how long any one step may take before the test fails instead of hanging
+const STEP: Duration = Duration::from_secs(5);
+
+fn client(server: &MockPushServer) -> EventStreamingClient {
+    EventStreamingClient::new(Environment::PC, "test".to_owned(), CallbackHolder::new())
+        .with_url(server.url())
+}
+
+fn login(character: u64) -> Value {
+    json!({
+        "event_name": "PlayerLogin",
+        "character_id": character.to_string(),
+        "timestamp": "1700000000",
+        "world_id": "17",
+    })
+}
+
+/// read responses until one matches, failing if the connection closes or it takes too long
+async fn next_matching<T>(
+    client: &mut EventStreamingClient,
+    mut matches: impl FnMut(&EventResponse) -> Option<T>,
+) -> T {
+    tokio::time::timeout(STEP, async {
+        loop {
+            match client.next_response().await {
+                Some(Ok(resp)) => {
+                    if let Some(value) = matches(&resp) {
+                        return value;
+                    }
+                }
+                Some(Err(_)) => (),
+                None => panic!("connection closed"),
+            }
+        }
+    })
+    .await
+    .expect("timed out waiting for a response")
+}
+
+fn subscription_confirmed(resp: &EventResponse) -> Option<()> {
+    match resp {
+        EventResponse::ExternallyTagged(ExternallyTaggedEventResponse::Subscription(_)) => Some(()),
+        _ => None,
+    }
+}
+
+fn login_character(resp: &EventResponse) -> Option<Character> {
+    match resp {
+        EventResponse::InternallyTagged(InternallyTaggedEventResponse::ServiceMessage {
+            payload: EventPayload::PlayerLogin(e),
+            ..
+        }) => Some(e.character_id),
+        _ => None,
+    }
+}
+
+#[tokio::test]
+async fn replays_the_net_subscription_after_a_disconnect() {
+    let server = MockPushServer::start().await.unwrap();
+    let mut client = client(&server).with_reconnect_policy(ReconnectPolicy {
+        initial_delay: Duration::from_millis(10),
+        ..Default::default()
+    });
+    client.connect().await.unwrap();
+
+    let requests = [
+        EventRequest::subscribe()
+            .world(World::Emerald)
+            .event(EventType::PlayerLogin)
+            .build(),
+        EventRequest::subscribe().world(World::Miller).build(),
+        EventRequest::clear_subscribe().world(World::Miller).build(),
+        EventRequest::subscribe().character(Character(5)).build(),
+        EventRequest::clear_subscribe()
+            .character(Character(5))
+            .build(),
+    ];
+    let sent = requests.len();
+    for request in requests {
+        client.send_request(request).await.unwrap();
+        next_matching(&mut client, subscription_confirmed).await;
+    }
+
+    server.disconnect();
+    next_matching(&mut client, subscription_confirmed).await;
+
+    let received = server.requests();
+    assert_eq!(received.len(), sent + 1, "only one request is replayed");
+    let replayed = &received[sent];
+    assert_eq!(replayed["action"], "subscribe");
+    assert_eq!(replayed["worlds"], json!([17]));
+    assert_eq!(replayed["eventNames"], json!(["PlayerLogin"]));
+    assert!(replayed.get("characters").is_none());
+
+    // events keep arriving on the new connection
+    server.send_event(login(1));
+    let character = next_matching(&mut client, login_character).await;
+    assert_eq!(character, Character(1));
+}
+
+#[tokio::test]
+async fn splits_concatenated_frames() {
+    let server = MockPushServer::start().await.unwrap();
+    let mut client = client(&server);
+    client.connect().await.unwrap();
+
+    server.send_events_concatenated([login(1), login(2), login(3)]);
+    let mut characters = vec![];
+    while characters.len() < 3 {
+        characters.push(next_matching(&mut client, login_character).await);
+    }
+    assert_eq!(characters, [1, 2, 3].map(Character));
+}
+
+#[tokio::test]
+async fn detects_a_stale_connection_once_heartbeats_stop() {
+    let server = MockPushServer::start().await.unwrap();
+    let mut client = client(&server).with_idle_timeout(Duration::from_millis(300));
+    client.connect().await.unwrap();
+
+    // heartbeats well inside the idle timeout keep the connection alive
+    server.set_heartbeat_interval(Some(Duration::from_millis(50)));
+    let alive = tokio::time::timeout(Duration::from_millis(800), async {
+        loop {
+            if let Some(Err(e)) = client.next_response().await {
+                return e;
+            }
+        }
+    })
+    .await;
+    assert!(alive.is_err(), "went stale while heartbeats were arriving");
+
+    server.set_heartbeat_interval(None);
+    let error = tokio::time::timeout(STEP, async {
+        loop {
+            match client.next_response().await {
+                Some(Err(e)) => return e,
+                Some(Ok(_)) => (),
+                None => panic!("connection closed without going stale"),
+            }
+        }
+    })
+    .await
+    .expect("never went stale");
+    assert!(matches!(error, Error::Stale { .. }), "{:?}", error);
+}
+
+#[tokio::test]
+async fn asks_for_recent_characters() {
+    let server = MockPushServer::start().await.unwrap();
+    server.set_recent_characters(vec![Character(5), Character(6)]);
+    let mut client = client(&server);
+    client.connect().await.unwrap();
+
+    let ids = tokio::time::timeout(STEP, client.recent_character_ids())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(ids, [Character(5), Character(6)]);
+
+    let count = tokio::time::timeout(STEP, client.recent_character_ids_count())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(count, 2);
+}
+
+#[tokio::test]
+async fn run_returns_the_error_once_reconnecting_gives_up() {
+    let server = MockPushServer::start().await.unwrap();
+    let mut client = client(&server).with_reconnect_policy(ReconnectPolicy {
+        initial_delay: Duration::from_millis(10),
+        max_retries: Some(2),
+        ..Default::default()
+    });
+    client.connect().await.unwrap();
+
+    // nothing is listening on the port any more, so every attempt fails
+    drop(server);
+    let result = tokio::time::timeout(STEP, client.run())
+        .await
+        .expect("kept running after reconnecting failed");
+    assert!(matches!(result, Err(Error::WebSocket(_))), "{:?}", result);
+}
+
+#[tokio::test]
+async fn finishes_a_reconnect_interrupted_by_a_timeout() {
+    let server = MockPushServer::start().await.unwrap();
+    let mut client = client(&server).with_reconnect_policy(ReconnectPolicy {
+        initial_delay: Duration::from_millis(100),
+        jitter: 0.0,
+        ..Default::default()
+    });
+    client.connect().await.unwrap();
+    client
+        .send_request(
+            EventRequest::subscribe()
+                .world(World::Emerald)
+                .event(EventType::PlayerLogin)
+                .build(),
+        )
+        .await
+        .unwrap();
+    next_matching(&mut client, subscription_confirmed).await;
+
+    // gives up while the client is waiting to reconnect
+    server.disconnect();
+    let interrupted = tokio::time::timeout(Duration::from_millis(50), async {
+        while client.next_response().await.is_some() {}
+    })
+    .await;
+    assert!(interrupted.is_err(), "stopped instead of reconnecting");
+
+    next_matching(&mut client, subscription_confirmed).await;
+    assert_eq!(server.requests().len(), 2, "the subscription is replayed");
+    server.send_event(login(1));
+    let character = next_matching(&mut client, login_character).await;
+    assert_eq!(character, Character(1));
+}
+
+#[tokio::test]
+async fn keeps_the_backoff_across_interrupted_reconnects() {
+    let server = MockPushServer::start().await.unwrap();
+    // attempts wait 20, 40 then 80ms, each interrupted wait only fits two of them
+    let mut client = client(&server).with_reconnect_policy(ReconnectPolicy {
+        initial_delay: Duration::from_millis(20),
+        multiplier: 2.0,
+        jitter: 0.0,
+        max_retries: Some(3),
+        ..Default::default()
+    });
+    client.connect().await.unwrap();
+
+    drop(server);
+    for _ in 0..10 {
+        // the client stops once it gives up
+        let wait = tokio::time::timeout(Duration::from_millis(100), async {
+            while client.next_response().await.is_some() {}
+        });
+        if wait.await.is_ok() {
+            return;
+        }
+    }
+    panic!("never gave up reconnecting");
+}
+
+#[tokio::test]
+async fn streams_borrow_the_client() {
+    let server = MockPushServer::start().await.unwrap();
+    let mut client = client(&server);
+    client.connect().await.unwrap();
+    client
+        .send_request(
+            EventRequest::subscribe()
+                .world(World::Emerald)
+                .event(EventType::PlayerLogin)
+                .build(),
+        )
+        .await
+        .unwrap();
+
+    server.send_event(login(1));
+    let mut events = client.event_stream();
+    let first = tokio::time::timeout(STEP, events.next()).await.unwrap();
+    assert!(
+        matches!(first, Some(EventPayload::PlayerLogin(_))),
+        "{:?}",
+        first
+    );
+    drop(events);
+
+    // the subscription can still change once the stream is dropped
+    client
+        .send_request(EventRequest::subscribe().world(World::Miller).build())
+        .await
+        .unwrap();
+    let mut responses = client.stream();
+    let confirmed = tokio::time::timeout(STEP, async {
+        loop {
+            match responses.next().await {
+                Some(Ok(resp)) => {
+                    if subscription_confirmed(&resp).is_some() {
+                        return;
+                    }
+                }
+                Some(Err(_)) => (),
+                None => panic!("connection closed"),
+            }
+        }
+    })
+    .await;
+    assert!(confirmed.is_ok(), "the new subscription wasn't confirmed");
+    drop(responses);
+    assert_eq!(client.subscriptions().requested().worlds.len(), 2);
+}